@@ -0,0 +1,303 @@
+//! Capturing draw calls for inspection, like golden tests asserting on the produced geometry.
+//!
+//! ```ignore
+//! gl.capture_draw_calls(true);
+//! // ... draw something
+//! gl.draw_headless();
+//!
+//! let draw_calls = gl.take_captured_draw_calls();
+//! assert_eq!(capture::serialize(&draw_calls), std::fs::read_to_string("golden.txt").unwrap());
+//! ```
+
+use crate::{DrawCall, DrawMode, GlPipeline, Vertex};
+use miniquad::RenderPass;
+use std::fmt;
+
+/// Copy of a draw call, as it was sent to the GPU or consumed by "draw_headless"
+#[derive(Clone, Debug, PartialEq)]
+pub struct DrawCallRecord {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>,
+    /// See `Texture2D::id`
+    pub texture: u32,
+    pub pipeline: GlPipeline,
+    pub draw_mode: DrawMode,
+    pub clip: Option<(i32, i32, i32, i32)>,
+    pub projection: glam::Mat4,
+    pub model: glam::Mat4,
+    /// Render passes are numbered in order of appearance in the capture, None is the screen
+    pub render_pass: Option<usize>,
+}
+
+pub(crate) struct Capture {
+    draw_calls: Vec<DrawCallRecord>,
+    render_passes: Vec<RenderPass>,
+}
+
+impl Capture {
+    pub fn new() -> Capture {
+        Capture {
+            draw_calls: vec![],
+            render_passes: vec![],
+        }
+    }
+
+    pub fn push(&mut self, dc: &DrawCall) {
+        let render_pass = dc.render_pass.map(|render_pass| {
+            match self
+                .render_passes
+                .iter()
+                .position(|pass| *pass == render_pass)
+            {
+                Some(ix) => ix,
+                None => {
+                    self.render_passes.push(render_pass);
+                    self.render_passes.len() - 1
+                }
+            }
+        });
+
+        self.draw_calls.push(DrawCallRecord {
+            vertices: dc.vertices().to_vec(),
+            indices: dc.indices().to_vec(),
+            texture: dc.texture.id(),
+            pipeline: dc.pipeline,
+            draw_mode: dc.draw_mode,
+            clip: dc.clip,
+            projection: dc.projection,
+            model: dc.model,
+            render_pass,
+        });
+    }
+
+    pub fn take(&mut self) -> Vec<DrawCallRecord> {
+        self.render_passes.clear();
+
+        std::mem::take(&mut self.draw_calls)
+    }
+}
+
+#[derive(Debug)]
+pub struct CaptureError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl std::error::Error for CaptureError {}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Draw calls capture error at line {}: {}",
+            self.line, self.msg
+        )
+    }
+}
+
+fn join<T: fmt::Display>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Line-based text format, stable enough to be kept in golden files.
+/// Floats are written in the shortest form that parses back to the exact same value.
+pub fn serialize(draw_calls: &[DrawCallRecord]) -> String {
+    let mut res = String::new();
+
+    for dc in draw_calls {
+        res.push_str("draw_call\n");
+        res.push_str(&format!("  mode {:?}\n", dc.draw_mode));
+        res.push_str(&format!("  pipeline {}\n", dc.pipeline.0));
+        res.push_str(&format!("  texture {}\n", dc.texture));
+        match dc.render_pass {
+            Some(pass) => res.push_str(&format!("  render_pass {}\n", pass)),
+            None => res.push_str("  render_pass -\n"),
+        }
+        match dc.clip {
+            Some((x, y, w, h)) => res.push_str(&format!("  clip {} {} {} {}\n", x, y, w, h)),
+            None => res.push_str("  clip -\n"),
+        }
+        res.push_str(&format!(
+            "  projection {}\n",
+            join(&dc.projection.to_cols_array())
+        ));
+        res.push_str(&format!("  model {}\n", join(&dc.model.to_cols_array())));
+        for vertex in &dc.vertices {
+            res.push_str(&format!(
+                "  vertex {} {} {}\n",
+                join(&vertex.pos),
+                join(&vertex.uv),
+                join(&vertex.color)
+            ));
+        }
+        res.push_str(&format!("  indices {}\n", join(&dc.indices)));
+    }
+
+    res
+}
+
+fn parse_values<T: std::str::FromStr>(values: &[&str], count: usize) -> Result<Vec<T>, String> {
+    if values.len() != count {
+        return Err(format!("expected {} values, got {}", count, values.len()));
+    }
+
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| format!("invalid value {:?}", value))
+        })
+        .collect()
+}
+
+fn parse_optional<T: std::str::FromStr>(
+    values: &[&str],
+    count: usize,
+) -> Result<Option<Vec<T>>, String> {
+    match values {
+        ["-"] => Ok(None),
+        values => parse_values(values, count).map(Some),
+    }
+}
+
+fn parse_matrix(values: &[&str]) -> Result<glam::Mat4, String> {
+    let values = parse_values::<f32>(values, 16)?;
+    let mut cols = [0.; 16];
+    cols.copy_from_slice(&values);
+
+    Ok(glam::Mat4::from_cols_array(&cols))
+}
+
+fn parse_line(dc: &mut DrawCallRecord, key: &str, values: &[&str]) -> Result<(), String> {
+    match key {
+        "mode" => {
+            dc.draw_mode = match values {
+                ["Triangles"] => DrawMode::Triangles,
+                ["Lines"] => DrawMode::Lines,
+                _ => return Err(format!("invalid draw mode {:?}", values.join(" "))),
+            }
+        }
+        "pipeline" => dc.pipeline = GlPipeline(parse_values(values, 1)?[0]),
+        "texture" => dc.texture = parse_values(values, 1)?[0],
+        "render_pass" => dc.render_pass = parse_optional(values, 1)?.map(|pass| pass[0]),
+        "clip" => {
+            dc.clip = parse_optional(values, 4)?.map(|clip| (clip[0], clip[1], clip[2], clip[3]))
+        }
+        "projection" => dc.projection = parse_matrix(values)?,
+        "model" => dc.model = parse_matrix(values)?,
+        "vertex" => {
+            if values.len() != 9 {
+                return Err(format!("expected 9 values, got {}", values.len()));
+            }
+            let floats = parse_values::<f32>(&values[..5], 5)?;
+            let color = parse_values::<u8>(&values[5..], 4)?;
+
+            dc.vertices.push(Vertex {
+                pos: [floats[0], floats[1], floats[2]],
+                uv: [floats[3], floats[4]],
+                color: [color[0], color[1], color[2], color[3]],
+            });
+        }
+        "indices" => dc.indices = parse_values(values, values.len())?,
+        _ => return Err(format!("unknown key {:?}", key)),
+    }
+
+    Ok(())
+}
+
+/// Parse the draw calls from the text produced by "serialize"
+pub fn deserialize(data: &str) -> Result<Vec<DrawCallRecord>, CaptureError> {
+    let mut draw_calls: Vec<DrawCallRecord> = vec![];
+
+    for (n, line) in data.lines().enumerate() {
+        let error = |msg: String| CaptureError { line: n + 1, msg };
+
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+        let (key, values) = match tokens.split_first() {
+            Some((key, values)) => (*key, values),
+            None => continue,
+        };
+
+        if key == "draw_call" {
+            draw_calls.push(DrawCallRecord {
+                vertices: vec![],
+                indices: vec![],
+                texture: 0,
+                pipeline: GlPipeline(0),
+                draw_mode: DrawMode::Triangles,
+                clip: None,
+                projection: glam::Mat4::identity(),
+                model: glam::Mat4::identity(),
+                render_pass: None,
+            });
+            continue;
+        }
+
+        let dc = draw_calls
+            .last_mut()
+            .ok_or_else(|| error("expected \"draw_call\"".to_string()))?;
+
+        parse_line(dc, key, values).map_err(error)?;
+    }
+
+    Ok(draw_calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Color, QuadGl};
+
+    #[test]
+    fn capture_headless() {
+        let mut gl = QuadGl::headless();
+        let texture = gl.headless_texture(2, 2, &[255; 16]);
+
+        gl.capture_draw_calls(true);
+        gl.geometry(
+            &[
+                Vertex::new(0., 0., 0., 0., 0., Color::new(1., 0., 0., 1.)),
+                Vertex::new(1. / 3., 0., 0., 1., 0., Color::new(1., 0., 0., 1.)),
+                Vertex::new(0., 10., 0., 0., 1., Color::new(1., 0., 0., 1.)),
+            ],
+            &[0, 1, 2],
+        );
+        gl.texture(Some(texture));
+        gl.scissor(Some((1, 2, 3, 4)));
+        gl.draw_mode(DrawMode::Lines);
+        gl.geometry(
+            &[
+                Vertex::new(0., 0., 0., 0., 0., Color::new(0., 1., 0., 0.5)),
+                Vertex::new(5., 5., 0., 1., 1., Color::new(0., 1., 0., 0.5)),
+            ],
+            &[0, 1],
+        );
+        gl.draw_headless();
+
+        let draw_calls = gl.take_captured_draw_calls();
+        assert_eq!(draw_calls.len(), 2);
+        assert_eq!(draw_calls[0].indices, &[0, 1, 2]);
+        assert_eq!(draw_calls[0].vertices[1].position(), [1. / 3., 0., 0.]);
+        assert_eq!(draw_calls[1].texture, texture.id());
+        assert_eq!(draw_calls[1].draw_mode, DrawMode::Lines);
+        assert_eq!(draw_calls[1].clip, Some((1, 2, 3, 4)));
+        assert_eq!(draw_calls[1].vertices[0].color(), [0, 255, 0, 127]);
+        assert!(gl.take_captured_draw_calls().is_empty());
+
+        let serialized = serialize(&draw_calls);
+        assert_eq!(deserialize(&serialized).unwrap(), draw_calls);
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        assert_eq!(deserialize("  mode Lines").unwrap_err().line, 1);
+        assert_eq!(deserialize("draw_call\n  clip 1 2 3").unwrap_err().line, 2);
+        assert!(deserialize("draw_call\n  vertex 0 0 0 0 0 255 255 255").is_err());
+        assert!(deserialize("draw_call\n  mode Points").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! CPU-side Image manipulations: cropping, blitting, flipping, rotating, resizing and color ops,
+//! and PNG encoding.
+//!
+//! Coordinates are in pixels, y = 0 is the first row of the data:
+//! the top one for loaded images, the bottom one for the screen data.
+
+use crate::{Color, FilterMode, Image};
+
+fn to_f32(pixel: [u8; 4]) -> [f32; 4] {
+    [
+        pixel[0] as f32 / 255.,
+        pixel[1] as f32 / 255.,
+        pixel[2] as f32 / 255.,
+        pixel[3] as f32 / 255.,
+    ]
+}
+
+fn to_u8(pixel: [f32; 4]) -> [u8; 4] {
+    let channel = |value: f32| (value.clamp(0., 1.) * 255.).round() as u8;
+
+    [
+        channel(pixel[0]),
+        channel(pixel[1]),
+        channel(pixel[2]),
+        channel(pixel[3]),
+    ]
+}
+
+impl Image {
+    /// Transparent image of the given size
+    fn with_size(width: u16, height: u16) -> Image {
+        Image {
+            bytes: vec![0; width as usize * height as usize * 4],
+            width,
+            height,
+        }
+    }
+
+    /// Image with each pixel computed from its coordinates
+    fn from_fn(width: u16, height: u16, f: impl Fn(usize, usize) -> [u8; 4]) -> Image {
+        let mut image = Image::with_size(width, height);
+        let data = image.get_image_data_mut();
+
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                data[y * width as usize + x] = f(x, y);
+            }
+        }
+        image
+    }
+
+    fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        self.get_image_data()[y * self.width as usize + x]
+    }
+
+    /// Copy of the given rectangle, clipped to the image bounds
+    pub fn sub_image(&self, x: u16, y: u16, width: u16, height: u16) -> Image {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+
+        Image::from_fn(width, height, |i, j| {
+            self.pixel(x as usize + i, y as usize + j)
+        })
+    }
+
+    /// Keep only the given rectangle, clipped to the image bounds
+    pub fn crop(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        *self = self.sub_image(x, y, width, height);
+    }
+
+    /// Draw "source" over this image with its top left corner at "x", "y", blending by alpha.
+    /// Parts outside of this image are skipped.
+    pub fn blit(&mut self, source: &Image, x: i32, y: i32) {
+        let width = self.width as i32;
+
+        for j in 0..source.height as i32 {
+            for i in 0..source.width as i32 {
+                let (dst_x, dst_y) = (x + i, y + j);
+                if dst_x < 0 || dst_y < 0 || dst_x >= width || dst_y >= self.height as i32 {
+                    continue;
+                }
+
+                let src = to_f32(source.pixel(i as usize, j as usize));
+                let pixel = &mut self.get_image_data_mut()[(dst_y * width + dst_x) as usize];
+                let dst = to_f32(*pixel);
+
+                let alpha = src[3] + dst[3] * (1. - src[3]);
+                if alpha == 0. {
+                    *pixel = [0; 4];
+                    continue;
+                }
+                let mut blended = [alpha; 4];
+                for c in 0..3 {
+                    blended[c] = (src[c] * src[3] + dst[c] * dst[3] * (1. - src[3])) / alpha;
+                }
+                *pixel = to_u8(blended);
+            }
+        }
+    }
+
+    /// Mirror left to right
+    pub fn flip_horizontal(&mut self) {
+        let width = self.width as usize;
+
+        for row in self.get_image_data_mut().chunks_exact_mut(width.max(1)) {
+            row.reverse();
+        }
+    }
+
+    /// Mirror first row to last row
+    pub fn flip_vertical(&mut self) {
+        let row_size = self.width as usize * 4;
+        let height = self.height as usize;
+
+        for y in 0..height / 2 {
+            let (top, bottom) = self.bytes.split_at_mut((height - y - 1) * row_size);
+            top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+    }
+
+    /// Rotate by 90 degrees clockwise, for an image with the top row first
+    pub fn rotate_cw(&mut self) {
+        let height = self.height as usize;
+
+        *self = Image::from_fn(self.height, self.width, |x, y| {
+            self.pixel(y, height - 1 - x)
+        });
+    }
+
+    /// Rotate by 90 degrees counter-clockwise, for an image with the top row first
+    pub fn rotate_ccw(&mut self) {
+        let width = self.width as usize;
+
+        *self = Image::from_fn(self.height, self.width, |x, y| self.pixel(width - 1 - y, x));
+    }
+
+    /// Scaled copy of the image, "Nearest" keeps pixel art crisp, "Linear" is bilinear
+    pub fn resize(&self, width: u16, height: u16, filter: FilterMode) -> Image {
+        if self.width == 0 || self.height == 0 {
+            return Image::with_size(width, height);
+        }
+
+        let scale_x = self.width as f32 / width as f32;
+        let scale_y = self.height as f32 / height as f32;
+        let max_x = self.width as usize - 1;
+        let max_y = self.height as usize - 1;
+
+        Image::from_fn(width, height, |x, y| {
+            // position of the pixel center in the source image
+            let src_x = (x as f32 + 0.5) * scale_x;
+            let src_y = (y as f32 + 0.5) * scale_y;
+
+            match filter {
+                FilterMode::Nearest => {
+                    self.pixel((src_x as usize).min(max_x), (src_y as usize).min(max_y))
+                }
+                FilterMode::Linear => {
+                    let src_x = (src_x - 0.5).max(0.);
+                    let src_y = (src_y - 0.5).max(0.);
+                    let (x0, y0) = ((src_x as usize).min(max_x), (src_y as usize).min(max_y));
+                    let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+                    let (tx, ty) = (src_x - x0 as f32, src_y - y0 as f32);
+
+                    let lerp = |a: [f32; 4], b: [f32; 4], t: f32| {
+                        let mut result = [0.; 4];
+                        for c in 0..4 {
+                            result[c] = a[c] + (b[c] - a[c]) * t;
+                        }
+                        result
+                    };
+                    let top = lerp(to_f32(self.pixel(x0, y0)), to_f32(self.pixel(x1, y0)), tx);
+                    let bottom = lerp(to_f32(self.pixel(x0, y1)), to_f32(self.pixel(x1, y1)), tx);
+
+                    to_u8(lerp(top, bottom, ty))
+                }
+            }
+        })
+    }
+
+    /// Multiply every pixel by "color", alpha included
+    pub fn tint(&mut self, color: Color) {
+        let color = [color.r, color.g, color.b, color.a];
+
+        for pixel in self.get_image_data_mut() {
+            let mut tinted = to_f32(*pixel);
+            for c in 0..4 {
+                tinted[c] *= color[c];
+            }
+            *pixel = to_u8(tinted);
+        }
+    }
+
+    /// Multiply color channels by alpha, for blending with premultiplied alpha
+    pub fn premultiply_alpha(&mut self) {
+        for pixel in self.get_image_data_mut() {
+            let mut premultiplied = to_f32(*pixel);
+            for c in 0..3 {
+                premultiplied[c] *= premultiplied[3];
+            }
+            *pixel = to_u8(premultiplied);
+        }
+    }
+
+    /// PNG file contents, the first row of the data is the top one of the PNG
+    pub fn to_png_bytes(&self) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+
+        image::png::PNGEncoder::new(&mut bytes).encode(
+            &self.bytes,
+            self.width as _,
+            self.height as _,
+            image::ColorType::RGBA(8),
+        )?;
+
+        Ok(bytes)
+    }
+
+    /// Save as a PNG file, the first row of the data is the top one of the PNG
+    pub fn save_png(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_png_bytes()?)
+    }
+
+    /// Save as a PNG file flipped vertically, for the screen data where the first row is the bottom one.
+    /// Panics on write errors, "save_png" keeps the row order and returns them.
+    pub fn export_png(&self, path: &str) {
+        let mut flipped = self.clone();
+        flipped.flip_vertical();
+
+        flipped.save_png(path).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x3 image with each pixel red channel being its index
+    fn indexed() -> Image {
+        Image::from_fn(2, 3, |x, y| [(y * 2 + x) as u8, 0, 0, 255])
+    }
+
+    fn reds(image: &Image) -> Vec<u8> {
+        image
+            .get_image_data()
+            .iter()
+            .map(|pixel| pixel[0])
+            .collect()
+    }
+
+    #[test]
+    fn image_ops() {
+        let image = indexed();
+        assert_eq!(reds(&image.sub_image(1, 1, 5, 1)), [3]);
+
+        let mut flipped = indexed();
+        flipped.flip_horizontal();
+        assert_eq!(reds(&flipped), [1, 0, 3, 2, 5, 4]);
+        flipped.flip_vertical();
+        assert_eq!(reds(&flipped), [5, 4, 3, 2, 1, 0]);
+
+        let mut rotated = indexed();
+        rotated.rotate_cw();
+        assert_eq!((rotated.width, rotated.height), (3, 2));
+        assert_eq!(reds(&rotated), [4, 2, 0, 5, 3, 1]);
+        rotated.rotate_ccw();
+        assert_eq!(reds(&rotated), reds(&image));
+
+        let resized = image.resize(4, 6, FilterMode::Nearest);
+        assert_eq!(resized.pixel(3, 5), image.pixel(1, 2));
+        let smooth = Image::from_fn(2, 1, |x, _| [x as u8 * 255, 0, 0, 255]).resize(
+            4,
+            1,
+            FilterMode::Linear,
+        );
+        assert_eq!(reds(&smooth), [0, 64, 191, 255]);
+
+        let mut canvas = Image::gen_image_color(2, 2, Color::new(0., 0., 1., 1.));
+        let half_red = Image::gen_image_color(1, 1, Color::new(1., 0., 0., 0.5));
+        canvas.blit(&half_red, 1, 1);
+        canvas.blit(&half_red, -1, 0);
+        assert_eq!(canvas.pixel(1, 1), [127, 0, 128, 255]);
+        assert_eq!(canvas.pixel(0, 0), [0, 0, 255, 255]);
+
+        let mut color = Image::gen_image_color(1, 1, Color::new(1., 1., 1., 0.5));
+        color.tint(Color::new(1., 0.5, 0., 1.));
+        assert_eq!(color.pixel(0, 0), [255, 128, 0, 127]);
+        color.premultiply_alpha();
+        assert_eq!(color.pixel(0, 0), [127, 64, 0, 127]);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let image = indexed();
+        let png = image.to_png_bytes().unwrap();
+
+        let decoded = Image::try_from_file_with_format(&png, None).unwrap();
+        assert_eq!(decoded.count_different_pixels(&image, 0), 0);
+    }
+}
@@ -1,5 +1,13 @@
 use miniquad::*;
 
+use std::collections::HashMap;
+
+pub mod capture;
+mod image_ops;
+pub mod rasterizer;
+
+pub use capture::DrawCallRecord;
+
 pub use colors::*;
 
 pub use miniquad::{FilterMode, ShaderError};
@@ -144,7 +152,7 @@ struct DrawCall {
     indices_count: usize,
 
     clip: Option<(i32, i32, i32, i32)>,
-    texture: Texture,
+    texture: Texture2D,
 
     model: glam::Mat4,
     projection: glam::Mat4,
@@ -155,7 +163,7 @@ struct DrawCall {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
 pub struct Vertex {
     pos: [f32; 3],
     uv: [f32; 2],
@@ -206,11 +214,23 @@ impl Vertex {
             ],
         }
     }
+
+    pub fn position(&self) -> [f32; 3] {
+        self.pos
+    }
+
+    pub fn uv(&self) -> [f32; 2] {
+        self.uv
+    }
+
+    pub fn color(&self) -> [u8; 4] {
+        self.color
+    }
 }
 
 impl DrawCall {
     fn new(
-        texture: Texture,
+        texture: Texture2D,
         projection: glam::Mat4,
         model: glam::Mat4,
         draw_mode: DrawMode,
@@ -393,7 +413,7 @@ impl MagicSnapshoter {
 }
 
 struct GlState {
-    texture: Texture,
+    texture: Texture2D,
     draw_mode: DrawMode,
     clip: Option<(i32, i32, i32, i32)>,
     projection: glam::Mat4,
@@ -401,7 +421,8 @@ struct GlState {
     pipeline: Option<GlPipeline>,
     depth_test_enable: bool,
 
-    snapshoter: MagicSnapshoter,
+    /// None for headless QuadGl
+    snapshoter: Option<MagicSnapshoter>,
 
     render_pass: Option<RenderPass>,
 }
@@ -421,8 +442,11 @@ struct Uniform {
 
 #[derive(Clone)]
 struct PipelineExt {
-    pipeline: miniquad::Pipeline,
+    /// None for pipelines of headless QuadGl, there is no GPU to make the real one on
+    pipeline: Option<miniquad::Pipeline>,
     wants_screen_texture: bool,
+    /// Used by the rasterizer, which can't read blend factors from the params
+    blend: bool,
     uniforms: Vec<Uniform>,
     uniforms_data: [u8; UNIFORMS_ARRAY_SIZE],
 }
@@ -439,9 +463,11 @@ impl PipelinesStorage {
     const TRIANGLES_DEPTH_PIPELINE: GlPipeline = GlPipeline(2);
     const LINES_DEPTH_PIPELINE: GlPipeline = GlPipeline(3);
 
-    fn new(ctx: &mut miniquad::Context) -> PipelinesStorage {
-        let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta())
-            .unwrap_or_else(|e| panic!("Failed to load shader: {}", e));
+    fn new(mut ctx: Option<&mut miniquad::Context>) -> PipelinesStorage {
+        let shader = ctx.as_mut().map(|ctx| {
+            Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta())
+                .unwrap_or_else(|e| panic!("Failed to load shader: {}", e))
+        });
 
         let params = PipelineParams {
             color_blend: Some(BlendState::new(
@@ -458,7 +484,7 @@ impl PipelinesStorage {
         };
 
         let triangles_pipeline = storage.make_pipeline(
-            ctx,
+            ctx.as_deref_mut(),
             shader,
             PipelineParams {
                 primitive_type: PrimitiveType::Triangles,
@@ -470,7 +496,7 @@ impl PipelinesStorage {
         assert_eq!(triangles_pipeline, Self::TRIANGLES_PIPELINE);
 
         let lines_pipeline = storage.make_pipeline(
-            ctx,
+            ctx.as_deref_mut(),
             shader,
             PipelineParams {
                 primitive_type: PrimitiveType::Lines,
@@ -482,7 +508,7 @@ impl PipelinesStorage {
         assert_eq!(lines_pipeline, Self::LINES_PIPELINE);
 
         let triangles_depth_pipeline = storage.make_pipeline(
-            ctx,
+            ctx.as_deref_mut(),
             shader,
             PipelineParams {
                 depth_write: true,
@@ -496,7 +522,7 @@ impl PipelinesStorage {
         assert_eq!(triangles_depth_pipeline, Self::TRIANGLES_DEPTH_PIPELINE);
 
         let lines_depth_pipeline = storage.make_pipeline(
-            ctx,
+            ctx.as_deref_mut(),
             shader,
             PipelineParams {
                 depth_write: true,
@@ -514,30 +540,69 @@ impl PipelinesStorage {
 
     fn make_pipeline(
         &mut self,
-        ctx: &mut Context,
-        shader: Shader,
+        ctx: Option<&mut Context>,
+        shader: Option<Shader>,
         params: PipelineParams,
         wants_screen_texture: bool,
         uniforms: Vec<(String, UniformType)>,
     ) -> GlPipeline {
-        let pipeline = Pipeline::with_params(
-            ctx,
-            &[BufferLayout::default()],
-            &[
-                VertexAttribute::new("position", VertexFormat::Float3),
-                VertexAttribute::new("texcoord", VertexFormat::Float2),
-                VertexAttribute::new("color0", VertexFormat::Byte4),
-            ],
-            shader,
-            params,
-        );
-
         let id = self
             .pipelines
             .iter()
             .position(|p| p.is_none())
             .unwrap_or_else(|| panic!("Pipelines amount exceeded"));
 
+        self.pipelines[id] = Some(Self::pipeline_ext(
+            ctx,
+            shader,
+            params,
+            wants_screen_texture,
+            uniforms,
+        ));
+        self.pipelines_amount += 1;
+
+        GlPipeline(id)
+    }
+
+    /// Replace the pipeline in place, uniform values are kept
+    fn rebuild_pipeline(
+        &mut self,
+        pipeline: GlPipeline,
+        ctx: Option<&mut Context>,
+        shader: Option<Shader>,
+        params: PipelineParams,
+        wants_screen_texture: bool,
+        uniforms: Vec<(String, UniformType)>,
+    ) {
+        let mut rebuilt = Self::pipeline_ext(ctx, shader, params, wants_screen_texture, uniforms);
+
+        if let Some(old) = &self.pipelines[pipeline.0] {
+            rebuilt.uniforms_data = old.uniforms_data;
+        }
+        self.pipelines[pipeline.0] = Some(rebuilt);
+    }
+
+    fn pipeline_ext(
+        ctx: Option<&mut Context>,
+        shader: Option<Shader>,
+        params: PipelineParams,
+        wants_screen_texture: bool,
+        uniforms: Vec<(String, UniformType)>,
+    ) -> PipelineExt {
+        let pipeline = ctx.zip(shader).map(|(ctx, shader)| {
+            Pipeline::with_params(
+                ctx,
+                &[BufferLayout::default()],
+                &[
+                    VertexAttribute::new("position", VertexFormat::Float3),
+                    VertexAttribute::new("texcoord", VertexFormat::Float2),
+                    VertexAttribute::new("color0", VertexFormat::Byte4),
+                ],
+                shader,
+                params,
+            )
+        });
+
         let uniforms = uniforms
             .iter()
             .scan(0, |offset, uniform| {
@@ -559,15 +624,13 @@ impl PipelinesStorage {
                 Some(uniform)
             })
             .collect();
-        self.pipelines[id] = Some(PipelineExt {
+        PipelineExt {
             pipeline,
             wants_screen_texture,
+            blend: params.color_blend.is_some(),
             uniforms,
             uniforms_data: [0; UNIFORMS_ARRAY_SIZE],
-        });
-        self.pipelines_amount += 1;
-
-        GlPipeline(id)
+        }
     }
 
     fn get(&self, draw_mode: DrawMode, depth_enabled: bool) -> GlPipeline {
@@ -601,14 +664,42 @@ pub struct QuadGl {
     state: GlState,
     start_time: f64,
 
-    white_texture: Texture,
+    white_texture: Texture2D,
+
+    headless: bool,
+    headless_textures: HashMap<u32, Image>,
+    headless_textures_count: u32,
+
+    capture: Option<capture::Capture>,
+    rasterizer: Option<rasterizer::Rasterizer>,
 }
 
 impl QuadGl {
     pub fn new(ctx: &mut miniquad::Context) -> QuadGl {
-        let white_texture = Texture::from_rgba8(ctx, 1, 1, &[255, 255, 255, 255]);
+        let white_texture = Texture2D::from_rgba8(ctx, 1, 1, &[255, 255, 255, 255]);
+        let snapshoter = MagicSnapshoter::new(ctx);
 
+        QuadGl::with_backend(Some(ctx), white_texture, Some(snapshoter))
+    }
+
+    /// QuadGl without any GPU behind it.
+    /// Works with headless textures and pipelines, and instead of being drawn
+    /// draw calls are consumed by "draw_headless".
+    pub fn headless() -> QuadGl {
+        let mut gl = QuadGl::with_backend(None, Texture2D::empty(), None);
+
+        gl.white_texture = gl.headless_texture(1, 1, &[255, 255, 255, 255]);
+        gl.state.texture = gl.white_texture;
+        gl
+    }
+
+    fn with_backend(
+        ctx: Option<&mut miniquad::Context>,
+        white_texture: Texture2D,
+        snapshoter: Option<MagicSnapshoter>,
+    ) -> QuadGl {
         QuadGl {
+            headless: ctx.is_none(),
             pipelines: PipelinesStorage::new(ctx),
             state: GlState {
                 clip: None,
@@ -618,7 +709,7 @@ impl QuadGl {
                 draw_mode: DrawMode::Triangles,
                 pipeline: None,
                 depth_test_enable: false,
-                snapshoter: MagicSnapshoter::new(ctx),
+                snapshoter,
                 render_pass: None,
             },
             draw_calls: Vec::with_capacity(200),
@@ -626,9 +717,17 @@ impl QuadGl {
             draw_calls_count: 0,
             start_time: miniquad::date::now(),
             white_texture,
+            headless_textures: HashMap::new(),
+            headless_textures_count: 0,
+            capture: None,
+            rasterizer: None,
         }
     }
 
+    pub fn is_headless(&self) -> bool {
+        self.headless
+    }
+
     pub fn make_pipeline(
         &mut self,
         ctx: &mut Context,
@@ -649,9 +748,132 @@ impl QuadGl {
         let shader = Shader::new(ctx, vertex_shader, fragment_shader, shader_meta)?;
         let wants_screen_texture = fragment_shader.find("_ScreenTexture").is_some();
 
-        Ok(self
-            .pipelines
-            .make_pipeline(ctx, shader, params, wants_screen_texture, uniforms))
+        Ok(self.pipelines.make_pipeline(
+            Some(ctx),
+            Some(shader),
+            params,
+            wants_screen_texture,
+            uniforms,
+        ))
+    }
+
+    /// Pipeline for headless QuadGl. Shaders are not compiled, so they are not validated either.
+    pub fn make_headless_pipeline(
+        &mut self,
+        fragment_shader: &str,
+        params: PipelineParams,
+        uniforms: Vec<(String, UniformType)>,
+    ) -> GlPipeline {
+        let wants_screen_texture = fragment_shader.find("_ScreenTexture").is_some();
+
+        self.pipelines
+            .make_pipeline(None, None, params, wants_screen_texture, uniforms)
+    }
+
+    /// Compile new shaders for the pipeline, keeping its GlPipeline valid and its uniform values.
+    /// On error the pipeline is left as it was.
+    pub fn rebuild_pipeline(
+        &mut self,
+        ctx: &mut Context,
+        pipeline: GlPipeline,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        params: PipelineParams,
+        uniforms: Vec<(String, UniformType)>,
+    ) -> Result<(), ShaderError> {
+        let mut shader_meta: ShaderMeta = shader::meta();
+
+        for uniform in &uniforms {
+            shader_meta
+                .uniforms
+                .uniforms
+                .push(UniformDesc::new(&uniform.0, uniform.1));
+        }
+
+        let shader = Shader::new(ctx, vertex_shader, fragment_shader, shader_meta)?;
+        let wants_screen_texture = fragment_shader.find("_ScreenTexture").is_some();
+
+        self.pipelines.rebuild_pipeline(
+            pipeline,
+            Some(ctx),
+            Some(shader),
+            params,
+            wants_screen_texture,
+            uniforms,
+        );
+
+        Ok(())
+    }
+
+    /// "rebuild_pipeline" for headless QuadGl
+    pub fn rebuild_headless_pipeline(
+        &mut self,
+        pipeline: GlPipeline,
+        fragment_shader: &str,
+        params: PipelineParams,
+        uniforms: Vec<(String, UniformType)>,
+    ) {
+        let wants_screen_texture = fragment_shader.find("_ScreenTexture").is_some();
+
+        self.pipelines.rebuild_pipeline(
+            pipeline,
+            None,
+            None,
+            params,
+            wants_screen_texture,
+            uniforms,
+        );
+    }
+
+    /// Texture stored in CPU memory, the only kind of textures headless QuadGl can use
+    pub fn headless_texture(&mut self, width: u16, height: u16, bytes: &[u8]) -> Texture2D {
+        assert!(self.headless, "Headless textures require headless QuadGl");
+        assert_eq!(width as usize * height as usize * 4, bytes.len());
+
+        self.headless_textures_count += 1;
+        let id = self.headless_textures_count;
+        self.headless_textures.insert(
+            id,
+            Image {
+                bytes: bytes.to_vec(),
+                width,
+                height,
+            },
+        );
+
+        let mut texture = miniquad::Texture::empty();
+        texture.width = width as u32;
+        texture.height = height as u32;
+
+        Texture2D {
+            texture,
+            headless_id: Some(id),
+        }
+    }
+
+    pub fn update_headless_texture(&mut self, texture: Texture2D, image: &Image) {
+        assert_eq!(texture.texture.width, image.width as u32);
+        assert_eq!(texture.texture.height, image.height as u32);
+
+        if let Some(data) = texture
+            .headless_id
+            .and_then(|id| self.headless_textures.get_mut(&id))
+        {
+            data.bytes.copy_from_slice(&image.bytes);
+        }
+    }
+
+    pub fn delete_headless_texture(&mut self, texture: Texture2D) {
+        if let Some(id) = texture.headless_id {
+            self.headless_textures.remove(&id);
+        }
+    }
+
+    /// Pixels of the headless texture, None for GPU or deleted textures
+    pub fn headless_texture_data(&self, texture: Texture2D) -> Option<&Image> {
+        texture
+            .headless_id
+            .and_then(|id| self.headless_textures.get(&id))
     }
 
     /// Reset only draw calls state
@@ -710,7 +932,9 @@ impl QuadGl {
             };
 
             if pipeline.wants_screen_texture {
-                self.state.snapshoter.snapshot(ctx, dc.render_pass);
+                if let Some(snapshoter) = self.state.snapshoter.as_mut() {
+                    snapshoter.snapshot(ctx, dc.render_pass);
+                }
             }
 
             if let Some(render_pass) = dc.render_pass {
@@ -722,13 +946,23 @@ impl QuadGl {
             bindings.vertex_buffers[0].update(ctx, dc.vertices());
             bindings.index_buffer.update(ctx, dc.indices());
 
-            bindings.images[0] = dc.texture;
-            bindings.images[1] = self.state.snapshoter.screen_texture.map_or_else(
-                || Texture::empty(),
-                |texture| texture.raw_miniquad_texture_handle(),
-            );
+            bindings.images[0] = dc.texture.texture;
+            bindings.images[1] = self
+                .state
+                .snapshoter
+                .as_ref()
+                .and_then(|snapshoter| snapshoter.screen_texture)
+                .map_or_else(
+                    || Texture::empty(),
+                    |texture| texture.raw_miniquad_texture_handle(),
+                );
 
-            ctx.apply_pipeline(&pipeline.pipeline);
+            ctx.apply_pipeline(
+                pipeline
+                    .pipeline
+                    .as_ref()
+                    .expect("Headless pipeline used with GPU context"),
+            );
             if let Some(clip) = dc.clip {
                 ctx.apply_scissor_rect(clip.0, height as i32 - (clip.1 + clip.3), clip.2, clip.3);
             } else {
@@ -744,6 +978,10 @@ impl QuadGl {
             });
             ctx.draw(0, dc.indices_count as i32, 1);
 
+            if let Some(capture) = self.capture.as_mut() {
+                capture.push(dc);
+            }
+
             dc.vertices_count = 0;
             dc.indices_count = 0;
 
@@ -753,6 +991,74 @@ impl QuadGl {
         self.draw_calls_count = 0;
     }
 
+    /// Headless counterpart of "draw": there is no GPU, so the draw calls are just consumed
+    pub fn draw_headless(&mut self) {
+        for dc in &mut self.draw_calls[0..self.draw_calls_count] {
+            if let Some(capture) = self.capture.as_mut() {
+                capture.push(dc);
+            }
+
+            if let Some(rasterizer) = self.rasterizer.as_mut() {
+                if dc.render_pass.is_none() {
+                    let textures = &self.headless_textures;
+                    let texture = dc.texture.headless_id.and_then(|id| textures.get(&id));
+                    let blend = self.pipelines.get_quad_pipeline(dc.pipeline).blend;
+
+                    rasterizer.draw(dc, texture, blend);
+                }
+            }
+
+            dc.vertices_count = 0;
+            dc.indices_count = 0;
+        }
+
+        self.draw_calls_count = 0;
+    }
+
+    /// Draw the following headless draw calls on the CPU into a "width" x "height" framebuffer,
+    /// see the "rasterizer" module for what is supported
+    pub fn enable_rasterizer(&mut self, width: u16, height: u16) {
+        assert!(self.headless, "Rasterizer requires headless QuadGl");
+
+        self.rasterizer = Some(rasterizer::Rasterizer::new(width, height));
+    }
+
+    pub fn disable_rasterizer(&mut self) {
+        self.rasterizer = None;
+    }
+
+    /// Fill the rasterizer framebuffer, does nothing without the rasterizer
+    pub fn clear_framebuffer(&mut self, color: Color) {
+        if let Some(rasterizer) = self.rasterizer.as_mut() {
+            rasterizer.clear(color);
+        }
+    }
+
+    /// Pixels drawn by the rasterizer.
+    /// Like with "glReadPixels", the first row is the bottom one.
+    pub fn framebuffer(&self) -> Option<&Image> {
+        self.rasterizer
+            .as_ref()
+            .map(|rasterizer| &rasterizer.framebuffer)
+    }
+
+    /// Start or stop keeping copies of all the drawn draw calls
+    pub fn capture_draw_calls(&mut self, capture: bool) {
+        self.capture = if capture {
+            Some(capture::Capture::new())
+        } else {
+            None
+        };
+    }
+
+    /// Draw calls drawn since the last "take_captured_draw_calls",
+    /// always empty if capturing is disabled
+    pub fn take_captured_draw_calls(&mut self) -> Vec<DrawCallRecord> {
+        self.capture
+            .as_mut()
+            .map_or_else(Vec::new, |capture| capture.take())
+    }
+
     pub fn get_projection_matrix(&self) -> glam::Mat4 {
         self.state.projection
     }
@@ -770,7 +1076,7 @@ impl QuadGl {
     }
 
     pub fn texture(&mut self, texture: Option<Texture2D>) {
-        self.state.texture = texture.map_or(self.white_texture, |t| t.texture);
+        self.state.texture = texture.unwrap_or(self.white_texture);
     }
 
     pub fn scissor(&mut self, clip: Option<(i32, i32, i32, i32)>) {
@@ -908,20 +1214,34 @@ impl QuadGl {
 }
 
 /// Texture, data stored in GPU memory
-#[derive(Clone, Copy, Debug)]
+/// or in CPU memory of headless QuadGl
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Texture2D {
     texture: miniquad::Texture,
+    /// Key in headless QuadGl textures storage, None for GPU textures
+    headless_id: Option<u32>,
 }
 
 impl Texture2D {
     pub fn from_miniquad_texture(texture: miniquad::Texture) -> Texture2D {
-        Texture2D { texture }
+        Texture2D {
+            texture,
+            headless_id: None,
+        }
     }
 
     pub fn empty() -> Texture2D {
-        Texture2D {
-            texture: miniquad::Texture::empty(),
-        }
+        Texture2D::from_miniquad_texture(miniquad::Texture::empty())
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.headless_id.is_some()
+    }
+
+    /// GL texture name, or id of the headless texture
+    pub fn id(&self) -> u32 {
+        self.headless_id
+            .unwrap_or_else(|| self.texture.gl_internal_id())
     }
 
     pub fn update(&mut self, ctx: &mut miniquad::Context, image: &Image) {
@@ -968,7 +1288,7 @@ impl Texture2D {
     ) -> Texture2D {
         let texture = miniquad::Texture::from_rgba8(ctx, width, height, &bytes);
 
-        Texture2D { texture }
+        Texture2D::from_miniquad_texture(texture)
     }
 
     pub fn set_filter(&self, ctx: &mut miniquad::Context, filter_mode: FilterMode) {
@@ -1010,6 +1330,7 @@ impl Texture2D {
 }
 
 /// Image, data stored in CPU memory
+#[derive(Clone)]
 pub struct Image {
     pub bytes: Vec<u8>,
     pub width: u16,
@@ -1018,24 +1339,27 @@ pub struct Image {
 
 impl Image {
     pub fn from_file_with_format(bytes: &[u8], format: Option<image::ImageFormat>) -> Image {
+        Image::try_from_file_with_format(bytes, format).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_from_file_with_format(
+        bytes: &[u8],
+        format: Option<image::ImageFormat>,
+    ) -> Result<Image, image::ImageError> {
         let img = if let Some(fmt) = format {
-            image::load_from_memory_with_format(&bytes, fmt)
-                .unwrap_or_else(|e| panic!("{}", e))
-                .to_rgba()
+            image::load_from_memory_with_format(&bytes, fmt)?.to_rgba()
         } else {
-            image::load_from_memory(&bytes)
-                .unwrap_or_else(|e| panic!("{}", e))
-                .to_rgba()
+            image::load_from_memory(&bytes)?.to_rgba()
         };
         let width = img.width() as u16;
         let height = img.height() as u16;
         let bytes = img.into_raw();
 
-        Image {
+        Ok(Image {
             width,
             height,
             bytes,
-        }
+        })
     }
 
     pub fn empty() -> Image {
@@ -1111,25 +1435,24 @@ impl Image {
         self.get_image_data()[(y * self.width as u32 + x) as usize].into()
     }
 
-    pub fn export_png(&self, path: &str) {
-        let mut bytes = vec![0; self.width as usize * self.height as usize * 4];
-
-        // flip the image before saving
-        for y in 0..self.height as usize {
-            for x in 0..self.width as usize * 4 {
-                bytes[y * self.width as usize * 4 + x] =
-                    self.bytes[(self.height as usize - y - 1) * self.width as usize * 4 + x];
-            }
-        }
+    /// Number of pixels with any channel differing by more than "tolerance",
+    /// for comparing screenshots with reference images
+    pub fn count_different_pixels(&self, other: &Image, tolerance: u8) -> usize {
+        assert_eq!(
+            (self.width, self.height),
+            (other.width, other.height),
+            "Compared images should be of the same size"
+        );
 
-        image::save_buffer(
-            path,
-            &bytes[..],
-            self.width as _,
-            self.height as _,
-            image::ColorType::RGBA(8),
-        )
-        .unwrap();
+        self.get_image_data()
+            .iter()
+            .zip(other.get_image_data())
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(a, b)| (*a as i16 - *b as i16).abs() > tolerance as i16)
+            })
+            .count()
     }
 }
 
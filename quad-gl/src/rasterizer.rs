@@ -0,0 +1,309 @@
+//! CPU rasterizer for headless QuadGl, making screenshot tests possible without a GPU.
+//!
+//! It covers what the default pipelines do: triangles and lines, textures, vertex colors,
+//! scissor and alpha blending. Everything else is simplified:
+//! - custom shaders are not executed, all pipelines are drawn as the default one,
+//!   blending only if the pipeline has any "color_blend"
+//! - textures are sampled with the nearest filter and clamped to the edge
+//! - depth test is ignored and draw calls into render passes are skipped
+//!
+//! The framebuffer follows GL conventions: the first row is the bottom one.
+
+use crate::{Color, DrawCall, DrawMode, Image};
+
+/// Vertex in framebuffer coordinates, with colors in 0..1
+#[derive(Clone, Copy)]
+struct WindowVertex {
+    x: f32,
+    y: f32,
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Drawable area, max is exclusive
+struct Bounds {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl Bounds {
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.min_x && x < self.max_x && y >= self.min_y && y < self.max_y
+    }
+}
+
+struct Shading<'a> {
+    texture: Option<&'a Image>,
+    blend: bool,
+}
+
+pub(crate) struct Rasterizer {
+    pub framebuffer: Image,
+}
+
+impl Rasterizer {
+    pub fn new(width: u16, height: u16) -> Rasterizer {
+        Rasterizer {
+            framebuffer: Image::gen_image_color(width, height, Color::new(0., 0., 0., 0.)),
+        }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        let color: [u8; 4] = color.into();
+
+        for pixel in self.framebuffer.get_image_data_mut() {
+            *pixel = color;
+        }
+    }
+
+    /// "texture" is the data of the draw call texture, None draws it as a white one
+    pub fn draw(&mut self, dc: &DrawCall, texture: Option<&Image>, blend: bool) {
+        let width = self.framebuffer.width as i32;
+        let height = self.framebuffer.height as i32;
+
+        // clip is x, y, w, h with y going down from the top of the screen
+        let bounds = match dc.clip {
+            Some((x, y, w, h)) => Bounds {
+                min_x: x.max(0),
+                min_y: (height - (y + h)).max(0),
+                max_x: (x + w).min(width),
+                max_y: (height - y).min(height),
+            },
+            None => Bounds {
+                min_x: 0,
+                min_y: 0,
+                max_x: width,
+                max_y: height,
+            },
+        };
+
+        let transform = dc.projection * dc.model;
+        let vertices = dc
+            .vertices()
+            .iter()
+            .map(|vertex| {
+                let clip = transform * glam::vec4(vertex.pos[0], vertex.pos[1], vertex.pos[2], 1.);
+
+                WindowVertex {
+                    x: (clip.x() / clip.w() + 1.) / 2. * width as f32,
+                    y: (clip.y() / clip.w() + 1.) / 2. * height as f32,
+                    uv: vertex.uv,
+                    color: [
+                        vertex.color[0] as f32 / 255.,
+                        vertex.color[1] as f32 / 255.,
+                        vertex.color[2] as f32 / 255.,
+                        vertex.color[3] as f32 / 255.,
+                    ],
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let shading = Shading { texture, blend };
+
+        match dc.draw_mode {
+            DrawMode::Triangles => {
+                for triangle in dc.indices().chunks_exact(3) {
+                    self.triangle(
+                        [
+                            vertices[triangle[0] as usize],
+                            vertices[triangle[1] as usize],
+                            vertices[triangle[2] as usize],
+                        ],
+                        &bounds,
+                        &shading,
+                    );
+                }
+            }
+            DrawMode::Lines => {
+                for line in dc.indices().chunks_exact(2) {
+                    self.line(
+                        vertices[line[0] as usize],
+                        vertices[line[1] as usize],
+                        &bounds,
+                        &shading,
+                    );
+                }
+            }
+        }
+    }
+
+    fn triangle(&mut self, vertices: [WindowVertex; 3], bounds: &Bounds, shading: &Shading) {
+        let [v0, mut v1, mut v2] = vertices;
+
+        let mut area = edge(&v0, &v1, v2.x, v2.y);
+        if area == 0. {
+            return;
+        }
+        // there is no face culling, so both windings are drawn
+        if area < 0. {
+            std::mem::swap(&mut v1, &mut v2);
+            area = -area;
+        }
+
+        let min_x = (v0.x.min(v1.x).min(v2.x).floor() as i32).max(bounds.min_x);
+        let min_y = (v0.y.min(v1.y).min(v2.y).floor() as i32).max(bounds.min_y);
+        let max_x = (v0.x.max(v1.x).max(v2.x).ceil() as i32).min(bounds.max_x);
+        let max_y = (v0.y.max(v1.y).max(v2.y).ceil() as i32).min(bounds.max_y);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+
+                let w0 = edge(&v1, &v2, px, py);
+                let w1 = edge(&v2, &v0, px, py);
+                let w2 = edge(&v0, &v1, px, py);
+
+                if !(covers(w0, &v1, &v2) && covers(w1, &v2, &v0) && covers(w2, &v0, &v1)) {
+                    continue;
+                }
+
+                let weights = [w0 / area, w1 / area, w2 / area];
+                let interpolate =
+                    |a: f32, b: f32, c: f32| a * weights[0] + b * weights[1] + c * weights[2];
+
+                let uv = [
+                    interpolate(v0.uv[0], v1.uv[0], v2.uv[0]),
+                    interpolate(v0.uv[1], v1.uv[1], v2.uv[1]),
+                ];
+                let mut color = [0.; 4];
+                for (i, channel) in color.iter_mut().enumerate() {
+                    *channel = interpolate(v0.color[i], v1.color[i], v2.color[i]);
+                }
+
+                self.shade(x, y, uv, color, shading);
+            }
+        }
+    }
+
+    fn line(&mut self, a: WindowVertex, b: WindowVertex, bounds: &Bounds, shading: &Shading) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let steps = dx.abs().max(dy.abs()).ceil().max(1.) as i32;
+
+        for step in 0..steps {
+            let t = (step as f32 + 0.5) / steps as f32;
+            let lerp = |from: f32, to: f32| from + (to - from) * t;
+
+            let x = lerp(a.x, b.x).floor() as i32;
+            let y = lerp(a.y, b.y).floor() as i32;
+            if !bounds.contains(x, y) {
+                continue;
+            }
+
+            let uv = [lerp(a.uv[0], b.uv[0]), lerp(a.uv[1], b.uv[1])];
+            let mut color = [0.; 4];
+            for (i, channel) in color.iter_mut().enumerate() {
+                *channel = lerp(a.color[i], b.color[i]);
+            }
+
+            self.shade(x, y, uv, color, shading);
+        }
+    }
+
+    /// The default fragment shader: texture color multiplied by the vertex color
+    fn shade(&mut self, x: i32, y: i32, uv: [f32; 2], color: [f32; 4], shading: &Shading) {
+        let texel = shading
+            .texture
+            .map_or([1.; 4], |texture| sample(texture, uv));
+        let width = self.framebuffer.width as usize;
+        let pixel = &mut self.framebuffer.get_image_data_mut()[y as usize * width + x as usize];
+
+        let alpha = texel[3] * color[3];
+        for i in 0..4 {
+            let src = texel[i] * color[i];
+            let value = if shading.blend {
+                src * alpha + pixel[i] as f32 / 255. * (1. - alpha)
+            } else {
+                src
+            };
+
+            pixel[i] = (value.clamp(0., 1.) * 255.).round() as u8;
+        }
+    }
+}
+
+/// Twice the signed area of the "a", "b", "p" triangle, positive when "p" is to the left of "ab"
+fn edge(a: &WindowVertex, b: &WindowVertex, px: f32, py: f32) -> f32 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+/// Top-left fill rule, so pixels on an edge shared by two triangles are drawn only once
+fn covers(w: f32, a: &WindowVertex, b: &WindowVertex) -> bool {
+    let top = a.y == b.y && b.x < a.x;
+    let left = b.y < a.y;
+
+    w > 0. || (w == 0. && (top || left))
+}
+
+fn sample(texture: &Image, uv: [f32; 2]) -> [f32; 4] {
+    let (width, height) = (texture.width(), texture.height());
+    if width == 0 || height == 0 {
+        return [1.; 4];
+    }
+
+    let x = ((uv[0] * width as f32).floor().max(0.) as usize).min(width - 1);
+    let y = ((uv[1] * height as f32).floor().max(0.) as usize).min(height - 1);
+    let texel = texture.get_image_data()[y * width + x];
+
+    [
+        texel[0] as f32 / 255.,
+        texel[1] as f32 / 255.,
+        texel[2] as f32 / 255.,
+        texel[3] as f32 / 255.,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{QuadGl, Vertex};
+
+    fn quad(gl: &mut QuadGl, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        gl.geometry(
+            &[
+                Vertex::new(x, y, 0., 0., 0., color),
+                Vertex::new(x + w, y, 0., 1., 0., color),
+                Vertex::new(x + w, y + h, 0., 1., 1., color),
+                Vertex::new(x, y + h, 0., 0., 1., color),
+            ],
+            &[0, 1, 2, 0, 2, 3],
+        );
+    }
+
+    #[test]
+    fn rasterize_headless() {
+        let mut gl = QuadGl::headless();
+        gl.enable_rasterizer(4, 4);
+        gl.set_projection_matrix(glam::Mat4::orthographic_rh_gl(0., 4., 4., 0., -1., 1.));
+        gl.clear_framebuffer(Color::new(0., 0., 1., 1.));
+
+        // top left half, blended over the blue background; the shared diagonal is blended once
+        quad(&mut gl, 0., 0., 2., 4., Color::new(1., 0., 0., 0.5));
+
+        // 2x1 texture, stretched over the right half and clipped to its bottom row
+        let texture = gl.headless_texture(2, 1, &[0, 255, 0, 255, 255, 255, 255, 255]);
+        gl.texture(Some(texture));
+        gl.scissor(Some((2, 3, 2, 1)));
+        quad(&mut gl, 2., 0., 2., 4., Color::new(1., 1., 1., 1.));
+        gl.draw_headless();
+
+        let framebuffer = gl.framebuffer().unwrap();
+        // GL row order: row 0 is the bottom of the screen
+        let pixel = |x: u32, y: u32| framebuffer.get_image_data()[(y * 4 + x) as usize];
+        for y in 0..4 {
+            assert_eq!(pixel(0, y), [127, 0, 128, 191]);
+            assert_eq!(pixel(1, y), [127, 0, 128, 191]);
+        }
+        assert_eq!(pixel(2, 0), [0, 255, 0, 255]);
+        assert_eq!(pixel(3, 0), [255, 255, 255, 255]);
+        assert_eq!(pixel(2, 1), [0, 0, 255, 255]);
+        assert_eq!(pixel(3, 3), [0, 0, 255, 255]);
+
+        let mut expected = Image::gen_image_color(4, 4, Color::new(0., 0., 1., 1.));
+        assert_eq!(framebuffer.count_different_pixels(&expected, 0), 10);
+        expected.set_pixel(2, 0, Color::new(0., 1., 0., 1.));
+        expected.set_pixel(3, 0, Color::new(1., 1., 1., 1.));
+        assert_eq!(framebuffer.count_different_pixels(&expected, 127), 0);
+    }
+}
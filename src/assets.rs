@@ -0,0 +1,263 @@
+//! Loading many files at once, for loading screens.
+//!
+//! ```ignore
+//! let mut assets = AssetManager::new();
+//! let player = assets.load_texture("player.png");
+//! let font = assets.load_ttf_font("font.ttf");
+//!
+//! while !assets.is_done() {
+//!     draw_text(&format!("Loading {:.0}%", assets.progress() * 100.), 20., 20., 30., WHITE);
+//!     next_frame().await;
+//! }
+//! let player = *assets.get(player).unwrap()?;
+//! ```
+//!
+//! All the files are requested right away, through the vfs mounts,
+//! and are loaded concurrently where the platform allows it.
+//! Loaded bytes are turned into assets on `update`, which `progress`, `is_done` and `load_all` call.
+
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::exec::FileError;
+use crate::text::{try_load_ttf_font_from_bytes, Font};
+use crate::texture::{load_texture_from_image, Image, Texture2D};
+use crate::Error;
+
+/// Typed reference to an asset of AssetManager
+pub struct Handle<T> {
+    id: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.id).finish()
+    }
+}
+
+type Response = Rc<RefCell<Option<Result<Vec<u8>, FileError>>>>;
+type Parse = Box<dyn Fn(&[u8]) -> Result<Box<dyn Any>, Error>>;
+
+enum Asset {
+    Loading { response: Response, parse: Parse },
+    Loaded(Box<dyn Any>),
+    Failed(Error),
+}
+
+/// Assets with the same path and type are loaded only once
+#[derive(Default)]
+pub struct AssetManager {
+    assets: Vec<Asset>,
+    paths: HashMap<(String, TypeId), usize>,
+}
+
+impl AssetManager {
+    pub fn new() -> AssetManager {
+        AssetManager::default()
+    }
+
+    /// Request the file and turn its bytes into an asset with "parse" once it is loaded
+    pub fn load<T: 'static>(
+        &mut self,
+        path: &str,
+        parse: fn(&[u8]) -> Result<T, Error>,
+    ) -> Handle<T> {
+        let key = (path.to_string(), TypeId::of::<T>());
+        let id = match self.paths.get(&key) {
+            Some(id) => *id,
+            None => {
+                let response: Response = Rc::new(RefCell::new(None));
+                {
+                    let response = response.clone();
+
+                    crate::file::vfs::load(
+                        path,
+                        Rc::new(move |bytes| {
+                            *response.borrow_mut() = Some(bytes);
+                        }),
+                    );
+                }
+
+                self.assets.push(Asset::Loading {
+                    response,
+                    parse: Box::new(move |bytes| {
+                        parse(bytes).map(|asset| Box::new(asset) as Box<dyn Any>)
+                    }),
+                });
+                self.paths.insert(key, self.assets.len() - 1);
+                self.assets.len() - 1
+            }
+        };
+
+        Handle {
+            id,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Handle<Vec<u8>> {
+        self.load(path, |bytes| Ok(bytes.to_vec()))
+    }
+
+    pub fn load_image(&mut self, path: &str) -> Handle<Image> {
+        self.load(path, |bytes| {
+            Ok(Image::try_from_file_with_format(bytes, None)?)
+        })
+    }
+
+    pub fn load_texture(&mut self, path: &str) -> Handle<Texture2D> {
+        self.load(path, |bytes| {
+            let image = Image::try_from_file_with_format(bytes, None)?;
+
+            Ok(load_texture_from_image(&image))
+        })
+    }
+
+    pub fn load_ttf_font(&mut self, path: &str) -> Handle<Font> {
+        self.load(path, try_load_ttf_font_from_bytes)
+    }
+
+    /// Turn the loaded files into assets
+    pub fn update(&mut self) {
+        for asset in &mut self.assets {
+            let loaded = match asset {
+                Asset::Loading { response, parse } => match response.borrow_mut().take() {
+                    Some(Ok(bytes)) => Some(match parse(&bytes) {
+                        Ok(asset) => Asset::Loaded(asset),
+                        Err(err) => Asset::Failed(err),
+                    }),
+                    Some(Err(err)) => Some(Asset::Failed(err.into())),
+                    None => None,
+                },
+                _ => None,
+            };
+
+            if let Some(loaded) = loaded {
+                *asset = loaded;
+            }
+        }
+    }
+
+    /// Part of the assets done loading, failed ones included, from 0 to 1
+    pub fn progress(&mut self) -> f32 {
+        self.update();
+
+        if self.assets.is_empty() {
+            return 1.;
+        }
+
+        let loading = self
+            .assets
+            .iter()
+            .filter(|asset| matches!(asset, Asset::Loading { .. }))
+            .count();
+
+        1. - loading as f32 / self.assets.len() as f32
+    }
+
+    pub fn is_done(&mut self) -> bool {
+        self.update();
+
+        self.assets
+            .iter()
+            .all(|asset| !matches!(asset, Asset::Loading { .. }))
+    }
+
+    /// Wait for all the requested assets, the error is the first failed one
+    pub async fn load_all(&mut self) -> Result<(), &Error> {
+        while !self.is_done() {
+            crate::window::next_frame().await;
+        }
+
+        match self.assets.iter().find_map(|asset| match asset {
+            Asset::Failed(err) => Some(err),
+            _ => None,
+        }) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// None while the asset is loading
+    pub fn get<T: 'static>(&self, handle: Handle<T>) -> Option<Result<&T, &Error>> {
+        match &self.assets[handle.id] {
+            Asset::Loading { .. } => None,
+            Asset::Loaded(asset) => Some(Ok(asset.downcast_ref().unwrap())),
+            Asset::Failed(err) => Some(Err(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+    use crate::window::next_frame;
+
+    #[test]
+    fn load_assets() {
+        let mut runner = Runner::new(100., 100., async {
+            let mut assets = AssetManager::new();
+            assert_eq!(assets.progress(), 1.);
+
+            let texture = assets.load_texture("src/font.png");
+            let image = assets.load_image("src/font.png");
+            let missing = assets.load_file("src/missing.png");
+            let corrupt = assets.load_texture("Cargo.toml");
+            let not_font = assets.load_ttf_font("src/font.png");
+            assert_eq!(assets.load_texture("src/font.png"), texture);
+
+            assert!(assets.load_all().await.is_err());
+            assert_eq!(assets.progress(), 1.);
+
+            let texture = *assets.get(texture).unwrap().unwrap();
+            let image = assets.get(image).unwrap().unwrap();
+            assert_eq!(texture.width(), image.width() as f32);
+            match assets.get(missing).unwrap() {
+                Err(Error::File(err)) => assert_eq!(err.path, "src/missing.png"),
+                _ => panic!("expected a file error"),
+            }
+            assert!(matches!(
+                assets.get(corrupt).unwrap(),
+                Err(Error::ImageDecode(_))
+            ));
+            assert!(matches!(
+                assets.get(not_font).unwrap(),
+                Err(Error::FontParse(_))
+            ));
+
+            next_frame().await
+        });
+
+        runner.run_frames(2);
+        assert!(runner.is_finished());
+    }
+}
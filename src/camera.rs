@@ -202,7 +202,7 @@ pub fn set_camera<T: Camera>(camera: T) {
     // flush previous camera draw calls
     context
         .draw_context
-        .perform_render_passes(&mut context.quad_context);
+        .perform_render_passes(context.quad_context.as_mut());
 
     context.draw_context.current_pass = camera.render_pass();
     context.draw_context.gl.render_pass(camera.render_pass());
@@ -210,7 +210,7 @@ pub fn set_camera<T: Camera>(camera: T) {
     context.draw_context.camera_matrix = Some(camera.matrix());
     context
         .draw_context
-        .update_projection_matrix(&mut context.quad_context);
+        .update_projection_matrix(context.screen_width, context.screen_height);
 }
 
 /// Reset default 2D camera mode
@@ -220,7 +220,7 @@ pub fn set_default_camera() {
     // flush previous camera draw calls
     context
         .draw_context
-        .perform_render_passes(&mut context.quad_context);
+        .perform_render_passes(context.quad_context.as_mut());
 
     context.draw_context.current_pass = None;
     context.draw_context.gl.render_pass(None);
@@ -228,5 +228,5 @@ pub fn set_default_camera() {
     context.draw_context.camera_matrix = None;
     context
         .draw_context
-        .update_projection_matrix(&mut context.quad_context);
+        .update_projection_matrix(context.screen_width, context.screen_height);
 }
@@ -1,91 +1,468 @@
 //! The way to emulate multitasking with macroquad's `.await`.
 //! Usefull for organizing state machines, animation cutscenes and other stuff that require
 //! some evaluation over time.
-//! 
-//! Unfortunately right now it is only partially safe. Functions that may violate rust safety guarantees are markes as unsafe though. Use them with extra care, additional contracts not checked by the type system are specified in each of those function documentation. 
+//!
+//! Coroutines are polled once per frame, right after the main future.
+//! They may start and stop other coroutines, or stop themselves.
+//! `Coroutine` handles stay valid forever: once a coroutine is done its slot is reused,
+//! but the old handle keeps reporting it as done.
+//!
+//! Coroutines started from a coroutine are its children: they are stopped along with it.
+//! Coroutines may be grouped into named scopes, to be stopped or paused together:
+//! ```ignore
+//! start_coroutine_in_scope("level", enemy_spawner());
+//! start_coroutine_in_scope("ui", animate_score());
+//!
+//! // level is unloaded, the UI keeps going
+//! stop_scope("level");
+//! ```
+//!
+//! Coroutines may return values, and can be awaited:
+//! ```ignore
+//! let level = start_coroutine(async { load_level("level1.txt").await });
+//! let music = start_coroutine(async { load_sound("music.ogg").await });
+//!
+//! // both are loading in parallel while the main future is waiting
+//! let (level, music) = (level.await.unwrap(), music.await.unwrap());
+//! ```
+//!
+//! A result is kept until it is retrieved or awaited.
+//! Handles are `Copy`, so the results nobody is going to read should be dropped with `Coroutine::detach`.
 
+use std::any::Any;
 use std::future::Future;
+use std::marker::PhantomData;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use crate::exec::ExecState;
 use crate::get_context;
+use crate::time::{get_clock_time, get_frame_count, get_frame_time, Clock};
+
+mod channel;
+mod combinators;
+pub mod tweens;
+
+pub use channel::{channel, Receiver, Recv, Sender, Signal, SignalFuture};
+pub use combinators::{join_all, race, select, Either, JoinAll, Race, Select};
+
+enum Slot {
+    Free,
+    Active(Pin<Box<dyn Future<Output = Box<dyn Any>>>>),
+    /// The future is taken out of the slot while being polled
+    Polling,
+    /// Finished, the result is kept until retrieved
+    Done(Box<dyn Any>),
+}
+
+struct Entry {
+    generation: u32,
+    slot: Slot,
+    /// The result is dropped as soon as the coroutine is finished
+    detached: bool,
+    /// Id and generation of the coroutine that started this one
+    parent: Option<(usize, u32)>,
+    /// Index in "scopes"
+    scope: Option<usize>,
+}
+
+struct Scope {
+    name: String,
+    paused: bool,
+}
 
 pub(crate) struct CoroutinesContext {
-    futures: Vec<Option<(Pin<Box<dyn Future<Output = ()>>>, ExecState)>>,
+    entries: Vec<Entry>,
+    free: Vec<usize>,
+    scopes: Vec<Scope>,
+    /// Id and generation of the coroutine being polled
+    current: Option<(usize, u32)>,
 }
 
 impl CoroutinesContext {
     pub fn new() -> CoroutinesContext {
-        CoroutinesContext { futures: Vec::with_capacity(1000) }
+        CoroutinesContext {
+            entries: Vec::with_capacity(1000),
+            free: vec![],
+            scopes: vec![],
+            current: None,
+        }
+    }
+
+    fn scope(&mut self, name: &str) -> usize {
+        match self.scopes.iter().position(|scope| scope.name == name) {
+            Some(ix) => ix,
+            None => {
+                self.scopes.push(Scope {
+                    name: name.to_string(),
+                    paused: false,
+                });
+                self.scopes.len() - 1
+            }
+        }
+    }
+
+    /// Coroutines started from a coroutine go to its scope by default
+    fn start<T: 'static>(
+        &mut self,
+        future: Pin<Box<dyn Future<Output = Box<dyn Any>>>>,
+        scope: Option<usize>,
+    ) -> Coroutine<T> {
+        let parent = self.current;
+        let scope = scope.or_else(|| parent.and_then(|(id, _)| self.entries[id].scope));
+
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                self.entries.push(Entry {
+                    generation: 0,
+                    slot: Slot::Free,
+                    detached: false,
+                    parent: None,
+                    scope: None,
+                });
+                self.entries.len() - 1
+            }
+        };
+        let entry = &mut self.entries[id];
+        entry.slot = Slot::Active(future);
+        entry.detached = false;
+        entry.parent = parent;
+        entry.scope = scope;
+
+        Coroutine {
+            id,
+            generation: self.entries[id].generation,
+            _marker: PhantomData,
+        }
+    }
+
+    fn entry(&mut self, id: usize, generation: u32) -> Option<&mut Entry> {
+        Some(&mut self.entries[id]).filter(|entry| entry.generation == generation)
+    }
+
+    fn is_active(&mut self, id: usize, generation: u32) -> bool {
+        match self.entry(id, generation) {
+            Some(entry) => matches!(entry.slot, Slot::Active(_) | Slot::Polling),
+            None => false,
+        }
     }
 
-    pub fn update(&mut self) {
-        for future in &mut self.futures {
-            if let Some((f, context)) = future {
-                *context = ExecState::RunOnce;
+    fn take_result(&mut self, id: usize, generation: u32) -> Option<Box<dyn Any>> {
+        let entry = self.entry(id, generation)?;
+        if !matches!(entry.slot, Slot::Done(_)) {
+            return None;
+        }
+
+        match std::mem::replace(&mut entry.slot, Slot::Polling) {
+            Slot::Done(result) => {
+                self.free(id);
+                Some(result)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Free the slot, a future being polled right now is dropped after the poll.
+    /// Returns false if the slot is free already.
+    fn free(&mut self, id: usize) -> bool {
+        let entry = &mut self.entries[id];
+        if matches!(entry.slot, Slot::Free) {
+            return false;
+        }
+
+        entry.slot = Slot::Free;
+        entry.generation = entry.generation.wrapping_add(1);
+        self.free.push(id);
+        true
+    }
+
+    /// Free the slot along with the slots of all the coroutine descendants
+    fn stop(&mut self, id: usize) {
+        let mut stopped = vec![id];
+
+        while let Some(id) = stopped.pop() {
+            let parent = Some((id, self.entries[id].generation));
+            if !self.free(id) {
+                continue;
+            }
+
+            for (child, entry) in self.entries.iter().enumerate() {
+                if entry.parent == parent && !matches!(entry.slot, Slot::Free) {
+                    stopped.push(child);
+                }
+            }
+        }
+    }
+
+    fn is_paused(&self, id: usize) -> bool {
+        match self.entries[id].scope {
+            Some(scope) => self.scopes[scope].paused,
+            None => false,
+        }
+    }
+
+    /// Number of running coroutines
+    pub fn count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| matches!(entry.slot, Slot::Active(_) | Slot::Polling))
+            .count()
+    }
+}
+
+/// Poll each coroutine once.
+/// The context is not borrowed during the polls: coroutines use it themselves.
+pub(crate) fn update() {
+    let len = get_context().coroutines_context.entries.len();
 
-                let futures_context_ref: &mut _ = unsafe { std::mem::transmute(context) };
-                if matches!(f.as_mut().poll(futures_context_ref), Poll::Ready(_)) {
-                    *future = None;
+    for id in 0..len {
+        let (generation, mut future) = {
+            let context = &mut get_context().coroutines_context;
+            if context.is_paused(id) {
+                continue;
+            }
+
+            let entry = &mut context.entries[id];
+            match std::mem::replace(&mut entry.slot, Slot::Polling) {
+                Slot::Active(future) => (entry.generation, future),
+                slot => {
+                    entry.slot = slot;
+                    continue;
                 }
             }
+        };
+        get_context().coroutines_context.current = Some((id, generation));
+
+        let mut exec_state = ExecState::RunOnce;
+        let futures_context_ref: &mut _ = unsafe { std::mem::transmute(&mut exec_state) };
+        let result = future.as_mut().poll(futures_context_ref);
+
+        let context = &mut get_context().coroutines_context;
+        context.current = None;
+        // stopped during the poll, the slot may even be taken by a new coroutine already
+        if context.entries[id].generation != generation {
+            continue;
+        }
+        match result {
+            // nothing to keep for the coroutines without a result, or with nobody to read it
+            Poll::Ready(result) if result.is::<()>() || context.entries[id].detached => {
+                context.free(id);
+            }
+            Poll::Ready(result) => context.entries[id].slot = Slot::Done(result),
+            Poll::Pending => context.entries[id].slot = Slot::Active(future),
         }
     }
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Coroutine {
+/// Handle to a started coroutine, returning "T"
+pub struct Coroutine<T = ()> {
     id: usize,
+    generation: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Coroutine<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Coroutine<T> {}
+
+impl<T> PartialEq for Coroutine<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.generation == other.generation
+    }
+}
+
+impl<T> std::fmt::Debug for Coroutine<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Coroutine")
+            .field("id", &self.id)
+            .field("generation", &self.generation)
+            .finish()
+    }
 }
 
-impl Coroutine {
+impl<T: 'static> Coroutine<T> {
+    /// True once the coroutine is finished or stopped
     pub fn is_done(&self) -> bool {
-        let context = &get_context().coroutines_context;
-        
-        context.futures[self.id].is_none() 
+        !get_context()
+            .coroutines_context
+            .is_active(self.id, self.generation)
+    }
+
+    /// The value returned by the coroutine, None while it is running or if it was stopped.
+    /// The value is moved out, so only the first call after the coroutine is finished returns it.
+    ///
+    /// Results of `Coroutine<()>` are not kept, for them this is the same as "is_done".
+    pub fn retrieve(&self) -> Option<T> {
+        let context = &mut get_context().coroutines_context;
+
+        match context.take_result(self.id, self.generation) {
+            Some(result) => result.downcast().ok().map(|result| *result),
+            None if self.is_done() => unit(),
+            None => None,
+        }
+    }
+
+    /// Drop the result once the coroutine is finished, instead of keeping it for "retrieve".
+    /// For coroutines started only for their side effects, so their slots are reused.
+    /// The coroutine keeps running, and "is_done" still works.
+    pub fn detach(self) {
+        let context = &mut get_context().coroutines_context;
+
+        if let Some(entry) = context.entry(self.id, self.generation) {
+            match entry.slot {
+                Slot::Done(_) => {
+                    context.free(self.id);
+                }
+                _ => entry.detached = true,
+            }
+        }
+    }
+}
+
+/// Some(()) when T is (), None otherwise
+fn unit<T: 'static>() -> Option<T> {
+    let unit: Box<dyn Any> = Box::new(());
+
+    unit.downcast().ok().map(|unit| *unit)
+}
+
+/// Waits for the coroutine to be done, the output is the same as of "retrieve".
+/// Like loading a file, finishing the wait takes the rest of the frame.
+impl<T: 'static> Future for Coroutine<T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let context: &mut ExecState = unsafe { std::mem::transmute(context) };
+
+        if *context == ExecState::Waiting || !self.is_done() {
+            Poll::Pending
+        } else {
+            *context = ExecState::Waiting;
+            Poll::Ready(self.retrieve())
+        }
+    }
+}
+
+/// Start polling the future once per frame
+pub fn start_coroutine<T: 'static>(future: impl Future<Output = T> + 'static) -> Coroutine<T> {
+    get_context().coroutines_context.start(boxed(future), None)
+}
+
+/// Start the coroutine in a named scope, see "stop_scope" and "set_scope_paused".
+/// The scope is created on the first use.
+pub fn start_coroutine_in_scope<T: 'static>(
+    scope: &str,
+    future: impl Future<Output = T> + 'static,
+) -> Coroutine<T> {
+    let context = &mut get_context().coroutines_context;
+    let scope = context.scope(scope);
+
+    context.start(boxed(future), Some(scope))
+}
+
+fn boxed<T: 'static>(
+    future: impl Future<Output = T> + 'static,
+) -> Pin<Box<dyn Future<Output = Box<dyn Any>>>> {
+    Box::pin(async move { Box::new(future.await) as Box<dyn Any> })
+}
+
+/// Like "start_coroutine", but the future may borrow non-'static data.
+///
+/// # Safety
+/// Everything the future borrows should outlive the coroutine,
+/// stop it with "stop_coroutine" before the borrowed data is gone.
+pub unsafe fn start_coroutine_unchecked<'a, T: 'static>(
+    future: impl Future<Output = T> + 'a,
+) -> Coroutine<T> {
+    let boxed_future: Pin<Box<dyn Future<Output = Box<dyn Any>> + 'a>> =
+        Box::pin(async move { Box::new(future.await) as Box<dyn Any> });
+    let boxed_future: Pin<Box<dyn Future<Output = Box<dyn Any>>>> =
+        std::mem::transmute(boxed_future);
+
+    get_context().coroutines_context.start(boxed_future, None)
+}
+
+pub fn stop_all_coroutines() {
+    let context = &mut get_context().coroutines_context;
+
+    for id in 0..context.entries.len() {
+        context.free(id);
     }
 }
-    
-pub unsafe fn start_coroutine(future: impl Future<Output = ()>) -> Coroutine {
+
+/// Does nothing if the coroutine is already done.
+/// A finished coroutine's result is dropped.
+/// All the coroutines started by this one, and started by them, are stopped as well.
+pub fn stop_coroutine<T>(coroutine: Coroutine<T>) {
     let context = &mut get_context().coroutines_context;
 
-    let boxed_future: Pin<Box<dyn Future<Output = ()>>> = Box::pin(future);
-    let boxed_future = std::mem::transmute(boxed_future);
+    if context.entry(coroutine.id, coroutine.generation).is_some() {
+        context.stop(coroutine.id);
+    }
+}
 
-    context.futures.push(Some((
-        boxed_future,
-        ExecState::RunOnce,
-    )));
+/// Stop all the coroutines of the scope, and their children in other scopes
+pub fn stop_scope(scope: &str) {
+    let context = &mut get_context().coroutines_context;
+    let scope = context.scope(scope);
 
-    Coroutine {
-        id: context.futures.len() - 1,
+    for id in 0..context.entries.len() {
+        if context.entries[id].scope == Some(scope) {
+            context.stop(id);
+        }
     }
 }
 
-pub unsafe fn stop_all_coroutines() {
-   let context = &mut get_context().coroutines_context;
+/// Coroutines of a paused scope are not polled.
+/// Waits measured in time, like "wait_seconds", still count the time passed while paused.
+pub fn set_scope_paused(scope: &str, paused: bool) {
+    let context = &mut get_context().coroutines_context;
+    let scope = context.scope(scope);
 
-    context.futures.clear();
+    context.scopes[scope].paused = paused;
 }
 
-pub fn stop_coroutine(coroutine: Coroutine) {
+pub fn is_scope_paused(scope: &str) -> bool {
     let context = &mut get_context().coroutines_context;
+    let scope = context.scope(scope);
+
+    context.scopes[scope].paused
+}
 
-    context.futures[coroutine.id] = None;
+/// Number of coroutines that are not done yet
+pub fn active_coroutines_count() -> usize {
+    get_context().coroutines_context.count()
 }
 
 pub struct TimerDelayFuture {
     pub(crate) start_time: f64,
     pub(crate) time: f32,
+    pub(crate) clock: Clock,
 }
 impl Unpin for TimerDelayFuture {}
 
+impl TimerDelayFuture {
+    /// Measure the delay on the given clock instead of `Clock::Game`.
+    /// `Clock::Real` delay will not stop when the game is paused.
+    pub fn with_clock(self, clock: Clock) -> TimerDelayFuture {
+        TimerDelayFuture {
+            start_time: get_clock_time(clock),
+            clock,
+            ..self
+        }
+    }
+}
+
 impl Future for TimerDelayFuture {
     type Output = Option<()>;
 
     fn poll(self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
-        if miniquad::date::now() - self.start_time >= self.time as f64 {
+        if get_clock_time(self.clock) - self.start_time >= self.time as f64 {
             Poll::Ready(Some(()))
         } else {
             Poll::Pending
@@ -93,50 +470,371 @@ impl Future for TimerDelayFuture {
     }
 }
 
+/// Wait "time" seconds of game time, see `Clock::Game`.
+/// Game time is the sum of the frame times, so it stops on pause and is deterministic
+/// when frames are stepped with a fixed frame time.
 pub fn wait_seconds(time: f32) -> TimerDelayFuture {
     TimerDelayFuture {
-        start_time: miniquad::date::now(),
+        start_time: get_clock_time(Clock::Game),
         time,
+        clock: Clock::Game,
     }
 }
 
-/// Special built-in coroutines for modifying values over time.
-pub mod tweens {
-    use std::future::Future;
-    use std::pin::Pin;
-    use std::{ops::{Sub, Add, Mul}, task::{Context, Poll}};
+/// Finish a frame-based wait the way loading a file does:
+/// the wait is over only while the frame is not used up yet, and finishing it uses the frame up.
+fn finish_wait(context: &mut Context, done: impl FnOnce() -> bool) -> Poll<()> {
+    let context: &mut ExecState = unsafe { std::mem::transmute(context) };
 
-    pub struct LinearTweanFuture<T> where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {
-        var: *mut T,
-        from: T,
-        to: T,
-        start_time: f64,
-        time: f32,
+    if *context == ExecState::Waiting || !done() {
+        Poll::Pending
+    } else {
+        *context = ExecState::Waiting;
+        Poll::Ready(())
     }
-    impl<T> Unpin for LinearTweanFuture<T> where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {}
+}
 
-    impl<T> Future for LinearTweanFuture<T> where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {
-        type Output = ();
+pub struct FramesDelayFuture {
+    frame: u64,
+}
+impl Unpin for FramesDelayFuture {}
 
-        fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
-            let t = (miniquad::date::now() - self.start_time) / self.time as f64;
-            if t <= 1. {
-                unsafe { *self.var = self.from + (self.to - self.from) * t as f32 };
-                Poll::Pending
-            } else {
-                unsafe { *self.var = self.to };
-                Poll::Ready(())
-            }
+impl Future for FramesDelayFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        finish_wait(context, || get_frame_count() >= self.frame)
+    }
+}
+
+/// Wait "frames" frames, `wait_frames(1)` is the same as `next_frame()`
+pub fn wait_frames(frames: u64) -> FramesDelayFuture {
+    FramesDelayFuture {
+        frame: get_frame_count() + frames,
+    }
+}
+
+pub struct ConditionFuture<F: FnMut() -> bool> {
+    condition: F,
+}
+impl<F: FnMut() -> bool> Unpin for ConditionFuture<F> {}
+
+impl<F: FnMut() -> bool> Future for ConditionFuture<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        finish_wait(context, || (self.condition)())
+    }
+}
+
+/// Wait for the condition to become true, it is checked once per frame
+pub fn wait_until<F: FnMut() -> bool>(condition: F) -> ConditionFuture<F> {
+    ConditionFuture { condition }
+}
+
+/// Wait while the condition is true, it is checked once per frame
+pub fn wait_while<F: FnMut() -> bool>(mut condition: F) -> ConditionFuture<impl FnMut() -> bool> {
+    wait_until(move || !condition())
+}
+
+pub struct FrameTimeDelayFuture {
+    time_left: f32,
+    last_frame: u64,
+}
+impl Unpin for FrameTimeDelayFuture {}
+
+impl Future for FrameTimeDelayFuture {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let frame = get_frame_count();
+        if frame != self.last_frame {
+            self.last_frame = frame;
+            self.time_left -= get_frame_time();
         }
+
+        finish_wait(context, || self.time_left <= 0.)
+    }
+}
+
+/// Wait "time" seconds, summing up `get_frame_time` of the frames the future was polled on.
+/// Unlike "wait_seconds", the time passed while the future was created but not awaited yet
+/// does not count.
+pub fn wait_frame_seconds(time: f32) -> FrameTimeDelayFuture {
+    FrameTimeDelayFuture {
+        time_left: time,
+        last_frame: get_frame_count(),
     }
+}
 
-    pub fn linear<T>(x: &mut T, to: T, time: f32) -> LinearTweanFuture<T> where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {
-        LinearTweanFuture {
-            var: x as *mut _,
-            to,
-            from: *x,
-            time,
-            start_time: miniquad::date::now()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+    use crate::window::next_frame;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    async fn forever() {
+        loop {
+            next_frame().await
         }
     }
+
+    #[test]
+    fn lifecycle() {
+        let mut runner = Runner::new(100., 100., forever());
+
+        let finished = start_coroutine(async {});
+        let stopped = start_coroutine(forever());
+        runner.step();
+        assert!(finished.is_done());
+        assert!(!stopped.is_done());
+
+        stop_coroutine(stopped);
+        assert!(stopped.is_done());
+
+        // both slots are reused, but the old handles still see their coroutines as done
+        let first = start_coroutine(forever());
+        let second = start_coroutine(forever());
+        assert!(finished.is_done() && stopped.is_done());
+        assert_ne!(first, finished);
+        assert_ne!(second, stopped);
+
+        stop_coroutine(finished);
+        stop_coroutine(stopped);
+        runner.step();
+        assert!(!first.is_done() && !second.is_done());
+        assert_eq!(active_coroutines_count(), 2);
+
+        stop_all_coroutines();
+        assert!(first.is_done() && second.is_done());
+        assert_eq!(active_coroutines_count(), 0);
+    }
+
+    #[test]
+    fn results() {
+        let mut runner = Runner::new(100., 100., forever());
+
+        let value = start_coroutine(async {
+            next_frame().await;
+            next_frame().await;
+            42
+        });
+        let unit = start_coroutine(async {});
+        assert_eq!(value.retrieve(), None);
+        assert_eq!(unit.retrieve(), None);
+
+        runner.run_frames(2);
+        assert!(value.is_done());
+        assert_eq!(unit.retrieve(), Some(()));
+        assert_eq!(value.retrieve(), Some(42));
+        assert_eq!(value.retrieve(), None);
+
+        let stopped = start_coroutine(async { 1 });
+        stop_coroutine(stopped);
+        runner.step();
+        assert_eq!(stopped.retrieve(), None);
+
+        // detached before and after finishing, both slots are freed without a retrieve
+        let detached = start_coroutine(async { 2 });
+        let finished = start_coroutine(async { 3 });
+        detached.detach();
+        runner.step();
+        finished.detach();
+        assert!(detached.is_done() && finished.is_done());
+        assert_eq!(detached.retrieve(), None);
+        assert_eq!(finished.retrieve(), None);
+        let free = &get_context().coroutines_context.free;
+        assert!(free.contains(&detached.id) && free.contains(&finished.id));
+    }
+
+    #[test]
+    fn await_coroutines() {
+        let results = Rc::new(std::cell::RefCell::new(vec![]));
+
+        let mut runner = Runner::new(100., 100., {
+            let results = results.clone();
+            async move {
+                let slow = start_coroutine(async {
+                    for _ in 0..5 {
+                        next_frame().await;
+                    }
+                    "slow"
+                });
+                let fast = start_coroutine(async { "fast" });
+
+                let (index, first) = race(vec![slow, fast]).await;
+                results.borrow_mut().push((index, first));
+
+                let all = join_all(vec![slow, start_coroutine(async { "other" })]).await;
+                results.borrow_mut().push((all.len(), all[0]));
+                results.borrow_mut().push((all.len(), all[1]));
+
+                match select(next_frame(), start_coroutine(async { "never" })).await {
+                    Either::Left(()) => results.borrow_mut().push((0, None)),
+                    Either::Right(value) => results.borrow_mut().push((1, value)),
+                }
+
+                let frames = join_all(vec![next_frame(), next_frame()]).await;
+                results.borrow_mut().push((frames.len(), None));
+                forever().await
+            }
+        });
+
+        runner.run_frames(10);
+        assert_eq!(
+            *results.borrow(),
+            vec![
+                (1, Some("fast")),
+                (2, Some("slow")),
+                (2, Some("other")),
+                (0, None),
+                (2, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn frame_waits() {
+        let events = Rc::new(std::cell::RefCell::new(vec![]));
+        let flag = Rc::new(Cell::new(false));
+
+        let mut runner = Runner::new(100., 100., {
+            let events = events.clone();
+            let flag = flag.clone();
+            async move {
+                let start = get_frame_count();
+                wait_frames(3).await;
+                events
+                    .borrow_mut()
+                    .push(("frames", get_frame_count() - start));
+
+                wait_until(|| flag.get()).await;
+                events.borrow_mut().push(("until", get_frame_count()));
+                wait_while(|| flag.get()).await;
+                events.borrow_mut().push(("while", get_frame_count()));
+
+                let start = get_frame_count();
+                wait_frame_seconds(1.).await;
+                events
+                    .borrow_mut()
+                    .push(("seconds", get_frame_count() - start));
+                forever().await
+            }
+        });
+        runner.set_frame_time(0.25);
+
+        runner.run_frames(5);
+        flag.set(true);
+        runner.run_frames(2);
+        flag.set(false);
+        runner.step();
+
+        // paused frames have 0 frame time
+        crate::time::set_paused(true);
+        runner.run_frames(2);
+        crate::time::set_paused(false);
+        runner.run_frames(4);
+
+        assert_eq!(
+            *events.borrow(),
+            vec![("frames", 3), ("until", 5), ("while", 7), ("seconds", 6)]
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let mut runner = Runner::new(100., 100., forever());
+
+        let ticks = Rc::new(Cell::new(0));
+        let ui = start_coroutine_in_scope("ui", {
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    ticks.set(ticks.get() + 1);
+                    next_frame().await;
+                }
+            }
+        });
+
+        let child = Rc::new(Cell::new(None));
+        let grandchild = Rc::new(Cell::new(None));
+        let level = start_coroutine_in_scope("level", {
+            let child = child.clone();
+            let grandchild = grandchild.clone();
+            async move {
+                child.set(Some(start_coroutine(async move {
+                    grandchild.set(Some(start_coroutine(forever())));
+                    forever().await
+                })));
+                forever().await
+            }
+        });
+        runner.run_frames(2);
+        let (child, grandchild) = (child.get().unwrap(), grandchild.get().unwrap());
+        assert!(!child.is_done() && !grandchild.is_done());
+
+        set_scope_paused("ui", true);
+        assert!(is_scope_paused("ui"));
+        let paused_ticks = ticks.get();
+        runner.run_frames(3);
+        assert_eq!(ticks.get(), paused_ticks);
+        set_scope_paused("ui", false);
+        runner.step();
+        assert_eq!(ticks.get(), paused_ticks + 1);
+
+        // children are in the parent scope
+        stop_scope("level");
+        assert!(level.is_done() && child.is_done() && grandchild.is_done());
+        assert!(!ui.is_done());
+
+        // stopping a coroutine stops its children, but finishing does not
+        let orphan = Rc::new(Cell::new(None));
+        let parent = start_coroutine({
+            let orphan = orphan.clone();
+            async move {
+                orphan.set(Some(start_coroutine(forever())));
+            }
+        });
+        let stopped_child = Rc::new(Cell::new(None));
+        let stopped_parent = start_coroutine({
+            let stopped_child = stopped_child.clone();
+            async move {
+                stopped_child.set(Some(start_coroutine(forever())));
+                forever().await
+            }
+        });
+        runner.step();
+        stop_coroutine(stopped_parent);
+        assert!(parent.is_done() && !orphan.get().unwrap().is_done());
+        assert!(stopped_child.get().unwrap().is_done());
+    }
+
+    #[test]
+    fn stop_from_coroutine() {
+        let mut runner = Runner::new(100., 100., forever());
+
+        let handle = Rc::new(Cell::new(None));
+        let started = Rc::new(Cell::new(None));
+        let coroutine = start_coroutine({
+            let handle = handle.clone();
+            let started = started.clone();
+            async move {
+                next_frame().await;
+                started.set(Some(start_coroutine(forever())));
+                stop_coroutine(handle.get().unwrap());
+                next_frame().await;
+                unreachable!();
+            }
+        });
+        handle.set(Some(coroutine));
+
+        runner.run_frames(3);
+        assert!(coroutine.is_done());
+        // stopped as a child of the stopped coroutine
+        assert!(started.get().unwrap().is_done());
+        assert_eq!(active_coroutines_count(), 0);
+    }
 }
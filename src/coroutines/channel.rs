@@ -0,0 +1,310 @@
+//! Single-threaded channels and signals, for coroutines to talk to each other.
+//!
+//! ```ignore
+//! let (sender, receiver) = channel();
+//! let door_opened = Signal::new();
+//!
+//! start_coroutine({
+//!     let door_opened = door_opened.clone();
+//!     async move {
+//!         while let Some(line) = receiver.recv().await {
+//!             show_dialogue(line).await;
+//!         }
+//!         door_opened.emit(());
+//!     }
+//! });
+//!
+//! sender.send("Who's there?");
+//! drop(sender);
+//! door_opened.wait().await;
+//! ```
+//!
+//! Awaiting a value that is already there does not take any time,
+//! so all the queued values may be received in a single frame.
+//! Once the future had to wait for a value, receiving it uses up the frame, like `next_frame` does.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::exec::ExecState;
+
+/// Ready with the value if there is one, using up the frame if "waited" before
+fn poll_value<T>(context: &mut Context, waited: &mut bool, value: Option<T>) -> Poll<T> {
+    let context: &mut ExecState = unsafe { std::mem::transmute(context) };
+
+    match value {
+        Some(value) => {
+            if *waited {
+                *context = ExecState::Waiting;
+            }
+            Poll::Ready(value)
+        }
+        None => {
+            *waited = true;
+            Poll::Pending
+        }
+    }
+}
+
+struct Queue<T> {
+    values: VecDeque<T>,
+    senders: usize,
+    receiver_alive: bool,
+}
+
+pub struct Sender<T> {
+    queue: Rc<RefCell<Queue<T>>>,
+}
+
+pub struct Receiver<T> {
+    queue: Rc<RefCell<Queue<T>>>,
+}
+
+/// Unbounded channel with any number of senders (Sender is Clone) and one receiver
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let queue = Rc::new(RefCell::new(Queue {
+        values: VecDeque::new(),
+        senders: 1,
+        receiver_alive: true,
+    }));
+
+    (
+        Sender {
+            queue: queue.clone(),
+        },
+        Receiver { queue },
+    )
+}
+
+impl<T> Sender<T> {
+    /// Returns the value back if the receiver is dropped
+    pub fn send(&self, value: T) -> Result<(), T> {
+        let mut queue = self.queue.borrow_mut();
+
+        if queue.receiver_alive {
+            queue.values.push_back(value);
+            Ok(())
+        } else {
+            Err(value)
+        }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.queue.borrow_mut().senders += 1;
+
+        Sender {
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.queue.borrow_mut().senders -= 1;
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Next value, if there is any already
+    pub fn try_recv(&self) -> Option<T> {
+        self.queue.borrow_mut().values.pop_front()
+    }
+
+    /// Wait for the next value.
+    /// The output is None once all the senders are dropped and all the values are received.
+    pub fn recv(&self) -> Recv<T> {
+        Recv {
+            queue: self.queue.clone(),
+            waited: false,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.borrow_mut();
+
+        queue.receiver_alive = false;
+        queue.values.clear();
+    }
+}
+
+pub struct Recv<T> {
+    queue: Rc<RefCell<Queue<T>>>,
+    waited: bool,
+}
+
+impl<T> Unpin for Recv<T> {}
+
+impl<T> Future for Recv<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let value = {
+            let mut queue = self.queue.borrow_mut();
+
+            match queue.values.pop_front() {
+                Some(value) => Some(Some(value)),
+                None if queue.senders == 0 => Some(None),
+                None => None,
+            }
+        };
+
+        poll_value(context, &mut self.waited, value)
+    }
+}
+
+struct SignalState<T> {
+    emits: u64,
+    value: Option<T>,
+}
+
+/// Broadcast event: every "wait" started before an "emit" gets the emitted value.
+/// Clones are handles to the same signal.
+pub struct Signal<T = ()> {
+    state: Rc<RefCell<SignalState<T>>>,
+}
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self {
+        Signal {
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Default for Signal<T> {
+    fn default() -> Self {
+        Signal::new()
+    }
+}
+
+impl<T: Clone> Signal<T> {
+    pub fn new() -> Signal<T> {
+        Signal {
+            state: Rc::new(RefCell::new(SignalState {
+                emits: 0,
+                value: None,
+            })),
+        }
+    }
+
+    pub fn emit(&self, value: T) {
+        let mut state = self.state.borrow_mut();
+
+        state.emits += 1;
+        state.value = Some(value);
+    }
+
+    /// Wait for the next emit after this call.
+    /// If the signal was emitted several times before the future was polled, the output is the last value.
+    pub fn wait(&self) -> SignalFuture<T> {
+        SignalFuture {
+            state: self.state.clone(),
+            emits: self.state.borrow().emits,
+            waited: false,
+        }
+    }
+}
+
+pub struct SignalFuture<T> {
+    state: Rc<RefCell<SignalState<T>>>,
+    emits: u64,
+    waited: bool,
+}
+
+impl<T> Unpin for SignalFuture<T> {}
+
+impl<T: Clone> Future for SignalFuture<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let value = {
+            let state = self.state.borrow();
+
+            if state.emits > self.emits {
+                state.value.clone()
+            } else {
+                None
+            }
+        };
+
+        poll_value(context, &mut self.waited, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutines::start_coroutine;
+    use crate::headless::Runner;
+    use crate::time::get_frame_count;
+    use crate::window::next_frame;
+
+    #[test]
+    fn channel_and_signal() {
+        let (sender, receiver) = channel();
+        let signal = Signal::new();
+        let received = Rc::new(RefCell::new(vec![]));
+
+        let mut runner = Runner::new(100., 100., async {
+            loop {
+                next_frame().await
+            }
+        });
+
+        let consumer = start_coroutine({
+            let received = received.clone();
+            let signal = signal.clone();
+            async move {
+                while let Some(value) = receiver.recv().await {
+                    received.borrow_mut().push((value, get_frame_count()));
+                }
+                signal.emit("closed");
+            }
+        });
+        let waiters = (0..2)
+            .map(|_| {
+                let signal = signal.clone();
+                start_coroutine(async move { signal.wait().await })
+            })
+            .collect::<Vec<_>>();
+
+        runner.step();
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+        runner.step();
+        let second_sender = sender.clone();
+        drop(sender);
+        second_sender.send(3).unwrap();
+        runner.run_frames(2);
+        assert!(!consumer.is_done());
+
+        drop(second_sender);
+        runner.step();
+        runner.step();
+        assert!(consumer.is_done());
+        assert_eq!(*received.borrow(), vec![(1, 1), (2, 1), (3, 2)]);
+        for waiter in waiters {
+            assert_eq!(waiter.retrieve(), Some("closed"));
+        }
+    }
+
+    #[test]
+    fn dropped_receiver() {
+        let (sender, receiver) = channel();
+
+        sender.send(1).unwrap();
+        assert_eq!(receiver.try_recv(), Some(1));
+        assert_eq!(receiver.try_recv(), None);
+
+        drop(receiver);
+        assert_eq!(sender.send(2), Err(2));
+    }
+}
@@ -0,0 +1,169 @@
+//! Waiting for multiple futures at once.
+//!
+//! macroquad futures are polled with an `ExecState` instead of a waker:
+//! `next_frame` is ready only once per frame, while the state is `RunOnce`.
+//! Each combined future gets its own state, so each of them moves one frame forward per frame,
+//! the same as if it was awaited alone.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::exec::ExecState;
+
+/// Poll "future" as if it was awaited alone, returns true if it used up its frame
+fn poll_child<F: Future + ?Sized>(
+    future: Pin<&mut F>,
+    state: ExecState,
+) -> (Poll<F::Output>, bool) {
+    let mut child_state = state;
+    let child_context: &mut Context = unsafe { std::mem::transmute(&mut child_state) };
+    let poll = future.poll(child_context);
+
+    (poll, child_state == ExecState::Waiting)
+}
+
+pub struct JoinAll<F: Future> {
+    futures: Vec<Option<Pin<Box<F>>>>,
+    outputs: Vec<Option<F::Output>>,
+}
+
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let context: &mut ExecState = unsafe { std::mem::transmute(context) };
+        let this = &mut *self;
+
+        let mut waiting = false;
+        for (future, output) in this.futures.iter_mut().zip(this.outputs.iter_mut()) {
+            if let Some(pinned) = future {
+                let (poll, used_frame) = poll_child(pinned.as_mut(), *context);
+                waiting |= used_frame;
+
+                if let Poll::Ready(value) = poll {
+                    *output = Some(value);
+                    *future = None;
+                }
+            }
+        }
+        if waiting {
+            *context = ExecState::Waiting;
+        }
+
+        if this.futures.iter().all(Option::is_none) {
+            Poll::Ready(
+                this.outputs
+                    .iter_mut()
+                    .map(|output| output.take().unwrap())
+                    .collect(),
+            )
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Wait for all the futures, the outputs are in the same order as the futures
+pub fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> JoinAll<F> {
+    let futures: Vec<_> = futures
+        .into_iter()
+        .map(|future| Some(Box::pin(future)))
+        .collect();
+    let outputs = futures.iter().map(|_| None).collect();
+
+    JoinAll { futures, outputs }
+}
+
+pub struct Race<F: Future> {
+    futures: Vec<Pin<Box<F>>>,
+}
+
+impl<F: Future> Unpin for Race<F> {}
+
+impl<F: Future> Future for Race<F> {
+    type Output = (usize, F::Output);
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let context: &mut ExecState = unsafe { std::mem::transmute(context) };
+
+        let mut waiting = false;
+        let mut result = None;
+        for (n, future) in self.futures.iter_mut().enumerate() {
+            let (poll, used_frame) = poll_child(future.as_mut(), *context);
+            waiting |= used_frame;
+
+            if let Poll::Ready(value) = poll {
+                result = Some((n, value));
+                break;
+            }
+        }
+        if waiting {
+            *context = ExecState::Waiting;
+        }
+
+        match result {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Wait for the first finished future, the output is its index and its output.
+/// The rest of the futures are dropped, coroutines awaited this way are not stopped though,
+/// and keep their results until retrieved or detached.
+pub fn race<F: Future>(futures: impl IntoIterator<Item = F>) -> Race<F> {
+    Race {
+        futures: futures.into_iter().map(Box::pin).collect(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
+
+pub struct Select<A: Future, B: Future> {
+    left: Pin<Box<A>>,
+    right: Pin<Box<B>>,
+}
+
+impl<A: Future, B: Future> Unpin for Select<A, B> {}
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        let context: &mut ExecState = unsafe { std::mem::transmute(context) };
+
+        let state = *context;
+        let (left, left_used_frame) = poll_child(self.left.as_mut(), state);
+        let result = match left {
+            Poll::Ready(value) => Poll::Ready(Either::Left(value)),
+            Poll::Pending => {
+                let (right, right_used_frame) = poll_child(self.right.as_mut(), state);
+                if right_used_frame {
+                    *context = ExecState::Waiting;
+                }
+
+                right.map(Either::Right)
+            }
+        };
+        if left_used_frame {
+            *context = ExecState::Waiting;
+        }
+
+        result
+    }
+}
+
+/// "race" for two futures of different types
+pub fn select<A: Future, B: Future>(left: A, right: B) -> Select<A, B> {
+    Select {
+        left: Box::pin(left),
+        right: Box::pin(right),
+    }
+}
@@ -0,0 +1,587 @@
+//! Special built-in coroutines for modifying values over time.
+//!
+//! ```ignore
+//! let position = Tweened::new(vec2(0., 0.));
+//!
+//! start_coroutine(play(
+//!     Sequence::new()
+//!         .then(Tween::new(&position, vec2(100., 0.), 1.).ease(easing::cubic_out))
+//!         .then(wait(0.5))
+//!         .then(Tween::new(&position, vec2(100., 50.), 0.5).yoyo().repeat(4))
+//!         .then(callback(|| println!("done"))),
+//! ));
+//!
+//! loop {
+//!     draw_circle(position.get().x(), position.get().y(), 10., RED);
+//!     next_frame().await
+//! }
+//! ```
+//!
+//! Animations are driven by time, not by frames: "play" advances them by the clock time passed,
+//! and the time left after an animation is finished is carried over to the next one in a sequence.
+//! So the result of stepping an animation does not depend on the frame rate.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::ops::{Add, Mul, Sub};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+use crate::time::{get_clock_time, Clock};
+
+pub mod easing;
+
+use easing::Easing;
+
+/// Values that can be tweened: f32, Vec2, Vec3 etc.
+pub trait Tweenable:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self>
+{
+}
+
+impl<T> Tweenable for T where T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T> {}
+
+/// Shared value animated by tweens, clones are handles to the same value
+pub struct Tweened<T>(Rc<Cell<T>>);
+
+impl<T> Clone for Tweened<T> {
+    fn clone(&self) -> Self {
+        Tweened(self.0.clone())
+    }
+}
+
+impl<T: Copy> Tweened<T> {
+    pub fn new(value: T) -> Tweened<T> {
+        Tweened(Rc::new(Cell::new(value)))
+    }
+
+    pub fn get(&self) -> T {
+        self.0.get()
+    }
+
+    pub fn set(&self, value: T) {
+        self.0.set(value)
+    }
+}
+
+/// Something changing over time
+pub trait Animation {
+    /// Move "dt" seconds forward. Returns the part of "dt" left after the animation is finished,
+    /// or None while it is still running.
+    fn advance(&mut self, dt: f32) -> Option<f32>;
+}
+
+impl Animation for Box<dyn Animation> {
+    fn advance(&mut self, dt: f32) -> Option<f32> {
+        (**self).advance(dt)
+    }
+}
+
+pub struct Tween<T: Tweenable> {
+    target: Tweened<T>,
+    from: Option<T>,
+    to: T,
+    duration: f32,
+    easing: Easing,
+    /// Time left to wait before the start
+    delay: f32,
+    /// None repeats forever
+    repeat: Option<u32>,
+    yoyo: bool,
+    on_complete: Option<Box<dyn FnMut()>>,
+
+    /// Time into the current play
+    elapsed: f32,
+    /// Finished plays
+    plays: u32,
+    finished: bool,
+}
+
+impl<T: Tweenable> Tween<T> {
+    /// Tween the target to "to" over "duration" seconds, without easing by default
+    pub fn new(target: &Tweened<T>, to: T, duration: f32) -> Tween<T> {
+        Tween {
+            target: target.clone(),
+            from: None,
+            to,
+            duration,
+            easing: easing::linear,
+            delay: 0.,
+            repeat: Some(1),
+            yoyo: false,
+            on_complete: None,
+            elapsed: 0.,
+            plays: 0,
+            finished: false,
+        }
+    }
+
+    /// Start value, by default the target value at the moment the tween starts
+    pub fn from(self, from: T) -> Tween<T> {
+        Tween {
+            from: Some(from),
+            ..self
+        }
+    }
+
+    pub fn ease(self, easing: Easing) -> Tween<T> {
+        Tween { easing, ..self }
+    }
+
+    /// Wait before the start, only once for a repeated tween
+    pub fn delay(self, seconds: f32) -> Tween<T> {
+        Tween {
+            delay: seconds,
+            ..self
+        }
+    }
+
+    /// Play "count" times in total
+    pub fn repeat(self, count: u32) -> Tween<T> {
+        Tween {
+            repeat: Some(count.max(1)),
+            ..self
+        }
+    }
+
+    pub fn repeat_forever(self) -> Tween<T> {
+        Tween {
+            repeat: None,
+            ..self
+        }
+    }
+
+    /// Every other play goes backwards, from "to" to "from"
+    pub fn yoyo(self) -> Tween<T> {
+        Tween { yoyo: true, ..self }
+    }
+
+    /// Called once the last play is finished
+    pub fn on_complete(self, f: impl FnMut() + 'static) -> Tween<T> {
+        Tween {
+            on_complete: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    fn value(&self, from: T, play: u32, t: f32) -> T {
+        let t = if self.yoyo && play % 2 == 1 {
+            1. - t
+        } else {
+            t
+        };
+
+        from + (self.to - from) * (self.easing)(t)
+    }
+}
+
+impl<T: Tweenable> Animation for Tween<T> {
+    fn advance(&mut self, mut dt: f32) -> Option<f32> {
+        if self.finished {
+            return Some(dt);
+        }
+
+        if self.delay > 0. {
+            let delay = self.delay.min(dt);
+            self.delay -= delay;
+            dt -= delay;
+            if self.delay > 0. {
+                return None;
+            }
+        }
+
+        let target = &self.target;
+        let from = *self.from.get_or_insert_with(|| target.get());
+        self.elapsed += dt;
+
+        // a single advance may pass over several plays of a short tween
+        let plays = if self.duration > 0. {
+            (self.elapsed / self.duration).floor() as u32
+        } else {
+            u32::MAX
+        };
+
+        match self.repeat {
+            Some(repeat) if self.plays.saturating_add(plays) >= repeat => {
+                let left = self.elapsed - (repeat - self.plays) as f32 * self.duration.max(0.);
+
+                self.plays = repeat;
+                self.finished = true;
+                self.target.set(self.value(from, repeat - 1, 1.));
+                if let Some(on_complete) = self.on_complete.as_mut() {
+                    on_complete();
+                }
+
+                Some(left.max(0.))
+            }
+            _ if self.duration <= 0. => {
+                self.elapsed = 0.;
+                self.target.set(self.value(from, 0, 1.));
+
+                None
+            }
+            _ => {
+                // parity is all that matters for the forever repeated tweens
+                self.plays = self.plays.wrapping_add(plays);
+                self.elapsed -= plays as f32 * self.duration;
+                self.target
+                    .set(self.value(from, self.plays, self.elapsed / self.duration));
+
+                None
+            }
+        }
+    }
+}
+
+/// Animations played one after another
+#[derive(Default)]
+pub struct Sequence {
+    animations: Vec<Box<dyn Animation>>,
+    current: usize,
+}
+
+impl Sequence {
+    pub fn new() -> Sequence {
+        Sequence::default()
+    }
+
+    pub fn then(mut self, animation: impl Animation + 'static) -> Sequence {
+        self.animations.push(Box::new(animation));
+        self
+    }
+}
+
+impl Animation for Sequence {
+    fn advance(&mut self, mut dt: f32) -> Option<f32> {
+        while let Some(animation) = self.animations.get_mut(self.current) {
+            dt = animation.advance(dt)?;
+            self.current += 1;
+        }
+
+        Some(dt)
+    }
+}
+
+/// Animations played at the same time, finished when all of them are
+#[derive(Default)]
+pub struct Parallel {
+    animations: Vec<Option<Box<dyn Animation>>>,
+}
+
+impl Parallel {
+    pub fn new() -> Parallel {
+        Parallel::default()
+    }
+
+    pub fn with(mut self, animation: impl Animation + 'static) -> Parallel {
+        self.animations.push(Some(Box::new(animation)));
+        self
+    }
+}
+
+impl Animation for Parallel {
+    fn advance(&mut self, dt: f32) -> Option<f32> {
+        let mut running = false;
+        let mut left = dt;
+
+        for slot in &mut self.animations {
+            if let Some(animation) = slot {
+                match animation.advance(dt) {
+                    Some(animation_left) => {
+                        left = left.min(animation_left);
+                        *slot = None;
+                    }
+                    None => running = true,
+                }
+            }
+        }
+
+        if running {
+            None
+        } else {
+            Some(left)
+        }
+    }
+}
+
+pub struct Wait {
+    time_left: f32,
+}
+
+/// Do nothing for a while, for pauses in sequences
+pub fn wait(seconds: f32) -> Wait {
+    Wait { time_left: seconds }
+}
+
+impl Animation for Wait {
+    fn advance(&mut self, dt: f32) -> Option<f32> {
+        if dt >= self.time_left {
+            let left = dt - self.time_left;
+            self.time_left = 0.;
+            Some(left)
+        } else {
+            self.time_left -= dt;
+            None
+        }
+    }
+}
+
+pub struct Callback {
+    f: Option<Box<dyn FnOnce()>>,
+}
+
+/// Call "f" and finish immediately
+pub fn callback(f: impl FnOnce() + 'static) -> Callback {
+    Callback {
+        f: Some(Box::new(f)),
+    }
+}
+
+impl Animation for Callback {
+    fn advance(&mut self, dt: f32) -> Option<f32> {
+        if let Some(f) = self.f.take() {
+            f();
+        }
+
+        Some(dt)
+    }
+}
+
+/// Future advancing the animation by the clock time passed since the previous frame
+pub struct Play<A: Animation> {
+    animation: A,
+    clock: Clock,
+    last_time: Option<f64>,
+}
+
+impl<A: Animation> Unpin for Play<A> {}
+
+impl<A: Animation> Play<A> {
+    /// Play on the given clock instead of `Clock::Game`.
+    pub fn with_clock(self, clock: Clock) -> Play<A> {
+        Play { clock, ..self }
+    }
+}
+
+impl<A: Animation> Future for Play<A> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+        let now = get_clock_time(self.clock);
+        let dt = now - self.last_time.unwrap_or(now);
+        self.last_time = Some(now);
+
+        match self.animation.advance(dt as f32) {
+            Some(_) => Poll::Ready(()),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Play the animation, starting from the first poll
+pub fn play<A: Animation>(animation: A) -> Play<A> {
+    Play {
+        animation,
+        clock: Clock::Game,
+        last_time: None,
+    }
+}
+
+#[deprecated(note = "Holds a raw pointer to the value, use `Tween` with `Tweened` instead")]
+pub struct LinearTweanFuture<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    var: *mut T,
+    from: T,
+    to: T,
+    start_time: f64,
+    time: f32,
+    clock: Clock,
+}
+
+#[allow(deprecated)]
+impl<T> Unpin for LinearTweanFuture<T> where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>
+{
+}
+
+#[allow(deprecated)]
+impl<T> LinearTweanFuture<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    /// Run the tween on the given clock instead of `Clock::Game`.
+    pub fn with_clock(self, clock: Clock) -> LinearTweanFuture<T> {
+        LinearTweanFuture {
+            start_time: get_clock_time(clock),
+            clock,
+            ..self
+        }
+    }
+}
+
+#[allow(deprecated)]
+impl<T> Future for LinearTweanFuture<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Self::Output> {
+        let t = (get_clock_time(self.clock) - self.start_time) / self.time as f64;
+        if t <= 1. {
+            unsafe { *self.var = self.from + (self.to - self.from) * t as f32 };
+            Poll::Pending
+        } else {
+            unsafe { *self.var = self.to };
+            Poll::Ready(())
+        }
+    }
+}
+
+#[deprecated(note = "Holds a raw pointer to the value, use `Tween` with `Tweened` instead")]
+#[allow(deprecated)]
+pub fn linear<T>(x: &mut T, to: T, time: f32) -> LinearTweanFuture<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    LinearTweanFuture {
+        var: x as *mut _,
+        to,
+        from: *x,
+        time,
+        start_time: get_clock_time(Clock::Game),
+        clock: Clock::Game,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutines::start_coroutine;
+    use crate::headless::Runner;
+    use crate::window::next_frame;
+
+    #[test]
+    fn easing_endpoints() {
+        let easings: &[Easing] = &[
+            easing::linear,
+            easing::quad_in,
+            easing::quad_out,
+            easing::quad_in_out,
+            easing::cubic_in,
+            easing::cubic_out,
+            easing::cubic_in_out,
+            easing::quart_in,
+            easing::quart_out,
+            easing::quart_in_out,
+            easing::sine_in,
+            easing::sine_out,
+            easing::sine_in_out,
+            easing::expo_in,
+            easing::expo_out,
+            easing::expo_in_out,
+            easing::circ_in,
+            easing::circ_out,
+            easing::circ_in_out,
+            easing::back_in,
+            easing::back_out,
+            easing::back_in_out,
+            easing::elastic_in,
+            easing::elastic_out,
+            easing::elastic_in_out,
+            easing::bounce_in,
+            easing::bounce_out,
+            easing::bounce_in_out,
+        ];
+
+        for easing in easings {
+            assert!(easing(0.).abs() < 1e-5);
+            assert!((easing(1.) - 1.).abs() < 1e-5);
+        }
+        assert_eq!(easing::quad_in_out(0.25), 0.125);
+        assert!(easing::back_in(0.2) < 0.);
+    }
+
+    #[test]
+    fn tween_repeat_yoyo() {
+        let x = Tweened::new(0.);
+        let completed = Rc::new(Cell::new(0));
+
+        let mut tween = Tween::new(&x, 10., 1.)
+            .delay(0.5)
+            .yoyo()
+            .repeat(3)
+            .on_complete({
+                let completed = completed.clone();
+                move || completed.set(completed.get() + 1)
+            });
+
+        assert_eq!(tween.advance(0.5), None);
+        assert_eq!(tween.advance(0.25), None);
+        assert_eq!(x.get(), 2.5);
+        assert_eq!(tween.advance(1.), None);
+        assert_eq!(x.get(), 7.5);
+        assert_eq!(tween.advance(1.), None);
+        assert_eq!(x.get(), 2.5);
+        assert_eq!(completed.get(), 0);
+
+        assert_eq!(tween.advance(1.), Some(0.25));
+        assert_eq!(x.get(), 10.);
+        assert_eq!(completed.get(), 1);
+        assert_eq!(tween.advance(1.), Some(1.));
+    }
+
+    #[test]
+    fn sequence_and_parallel() {
+        let a = Tweened::new(0.);
+        let b = Tweened::new(0.);
+        let done = Rc::new(Cell::new(false));
+
+        let mut sequence = Sequence::new()
+            .then(Tween::new(&a, 1., 1.))
+            .then(wait(0.5))
+            .then(
+                Parallel::new()
+                    .with(Tween::new(&a, 0., 1.).ease(easing::quad_in))
+                    .with(Tween::new(&b, 4., 2.)),
+            )
+            .then(callback({
+                let done = done.clone();
+                move || done.set(true)
+            }));
+
+        assert_eq!(sequence.advance(1.25), None);
+        assert_eq!(a.get(), 1.);
+        assert_eq!(sequence.advance(0.5), None);
+        assert_eq!((a.get(), b.get()), (0.9375, 0.5));
+        assert!(!done.get());
+
+        assert_eq!(sequence.advance(2.), Some(0.25));
+        assert_eq!((a.get(), b.get()), (0., 4.));
+        assert!(done.get());
+    }
+
+    #[test]
+    fn play_tween() {
+        let x = Tweened::new(0.);
+
+        let mut runner = Runner::new(100., 100., async {
+            loop {
+                next_frame().await
+            }
+        });
+        runner.set_frame_time(0.25);
+
+        let coroutine = start_coroutine(play(Tween::new(&x, 8., 1.)));
+        runner.step();
+        assert_eq!(x.get(), 0.);
+        runner.run_frames(2);
+        assert_eq!(x.get(), 4.);
+        runner.run_frames(2);
+        assert_eq!(x.get(), 8.);
+        assert!(coroutine.is_done());
+    }
+}
@@ -0,0 +1,201 @@
+//! Standard easing functions, mapping the linear progress 0..1 to the eased one.
+//! All of them start at 0 and end at 1, "back" and "elastic" overshoot in between.
+//!
+//! Formulas follow Robert Penner's easing equations, see <https://easings.net>.
+
+use std::f32::consts::PI;
+
+/// Easing function, any `fn(f32) -> f32` can be used
+pub type Easing = fn(f32) -> f32;
+
+pub fn linear(t: f32) -> f32 {
+    t
+}
+
+pub fn quad_in(t: f32) -> f32 {
+    t * t
+}
+
+pub fn quad_out(t: f32) -> f32 {
+    1. - (1. - t) * (1. - t)
+}
+
+pub fn quad_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        2. * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(2) / 2.
+    }
+}
+
+pub fn cubic_in(t: f32) -> f32 {
+    t * t * t
+}
+
+pub fn cubic_out(t: f32) -> f32 {
+    1. - (1. - t).powi(3)
+}
+
+pub fn cubic_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        4. * t * t * t
+    } else {
+        1. - (-2. * t + 2.).powi(3) / 2.
+    }
+}
+
+pub fn quart_in(t: f32) -> f32 {
+    t.powi(4)
+}
+
+pub fn quart_out(t: f32) -> f32 {
+    1. - (1. - t).powi(4)
+}
+
+pub fn quart_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        8. * t.powi(4)
+    } else {
+        1. - (-2. * t + 2.).powi(4) / 2.
+    }
+}
+
+pub fn sine_in(t: f32) -> f32 {
+    1. - (t * PI / 2.).cos()
+}
+
+pub fn sine_out(t: f32) -> f32 {
+    (t * PI / 2.).sin()
+}
+
+pub fn sine_in_out(t: f32) -> f32 {
+    -((PI * t).cos() - 1.) / 2.
+}
+
+pub fn expo_in(t: f32) -> f32 {
+    if t <= 0. {
+        0.
+    } else {
+        2f32.powf(10. * t - 10.)
+    }
+}
+
+pub fn expo_out(t: f32) -> f32 {
+    if t >= 1. {
+        1.
+    } else {
+        1. - 2f32.powf(-10. * t)
+    }
+}
+
+pub fn expo_in_out(t: f32) -> f32 {
+    if t <= 0. {
+        0.
+    } else if t >= 1. {
+        1.
+    } else if t < 0.5 {
+        2f32.powf(20. * t - 10.) / 2.
+    } else {
+        (2. - 2f32.powf(-20. * t + 10.)) / 2.
+    }
+}
+
+pub fn circ_in(t: f32) -> f32 {
+    1. - (1. - t * t).max(0.).sqrt()
+}
+
+pub fn circ_out(t: f32) -> f32 {
+    (1. - (t - 1.) * (t - 1.)).max(0.).sqrt()
+}
+
+pub fn circ_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        (1. - (1. - (2. * t).powi(2)).max(0.).sqrt()) / 2.
+    } else {
+        ((1. - (-2. * t + 2.).powi(2)).max(0.).sqrt() + 1.) / 2.
+    }
+}
+
+const BACK: f32 = 1.70158;
+
+pub fn back_in(t: f32) -> f32 {
+    (BACK + 1.) * t * t * t - BACK * t * t
+}
+
+pub fn back_out(t: f32) -> f32 {
+    1. + (BACK + 1.) * (t - 1.).powi(3) + BACK * (t - 1.).powi(2)
+}
+
+pub fn back_in_out(t: f32) -> f32 {
+    let c = BACK * 1.525;
+
+    if t < 0.5 {
+        (2. * t).powi(2) * ((c + 1.) * 2. * t - c) / 2.
+    } else {
+        ((2. * t - 2.).powi(2) * ((c + 1.) * (t * 2. - 2.) + c) + 2.) / 2.
+    }
+}
+
+pub fn elastic_in(t: f32) -> f32 {
+    if t <= 0. {
+        0.
+    } else if t >= 1. {
+        1.
+    } else {
+        -(2f32.powf(10. * t - 10.)) * ((t * 10. - 10.75) * (2. * PI / 3.)).sin()
+    }
+}
+
+pub fn elastic_out(t: f32) -> f32 {
+    if t <= 0. {
+        0.
+    } else if t >= 1. {
+        1.
+    } else {
+        2f32.powf(-10. * t) * ((t * 10. - 0.75) * (2. * PI / 3.)).sin() + 1.
+    }
+}
+
+pub fn elastic_in_out(t: f32) -> f32 {
+    let c = 2. * PI / 4.5;
+
+    if t <= 0. {
+        0.
+    } else if t >= 1. {
+        1.
+    } else if t < 0.5 {
+        -(2f32.powf(20. * t - 10.) * ((20. * t - 11.125) * c).sin()) / 2.
+    } else {
+        2f32.powf(-20. * t + 10.) * ((20. * t - 11.125) * c).sin() / 2. + 1.
+    }
+}
+
+pub fn bounce_in(t: f32) -> f32 {
+    1. - bounce_out(1. - t)
+}
+
+pub fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+pub fn bounce_in_out(t: f32) -> f32 {
+    if t < 0.5 {
+        (1. - bounce_out(1. - 2. * t)) / 2.
+    } else {
+        (1. + bounce_out(2. * t - 1.)) / 2.
+    }
+}
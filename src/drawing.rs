@@ -13,25 +13,30 @@ pub struct DrawContext {
 }
 
 impl DrawContext {
-    pub fn new(ctx: &mut miniquad::Context) -> DrawContext {
+    /// Headless draw context when there is no miniquad context
+    pub fn new(ctx: Option<&mut miniquad::Context>, width: f32, height: f32) -> DrawContext {
         let mut draw_context = DrawContext {
             camera_matrix: None,
-            gl: QuadGl::new(ctx),
+            gl: match ctx {
+                Some(ctx) => QuadGl::new(ctx),
+                None => QuadGl::headless(),
+            },
             current_pass: None,
         };
 
-        draw_context.update_projection_matrix(ctx);
+        draw_context.update_projection_matrix(width, height);
 
         draw_context
     }
 
-    pub(crate) fn perform_render_passes(&mut self, ctx: &mut miniquad::Context) {
-        self.gl.draw(ctx);
+    pub(crate) fn perform_render_passes(&mut self, ctx: Option<&mut miniquad::Context>) {
+        match ctx {
+            Some(ctx) => self.gl.draw(ctx),
+            None => self.gl.draw_headless(),
+        }
     }
 
-    pub(crate) fn update_projection_matrix(&mut self, ctx: &mut miniquad::Context) {
-        let (width, height) = ctx.screen_size();
-
+    pub(crate) fn update_projection_matrix(&mut self, width: f32, height: f32) {
         let projection = if let Some(matrix) = self.camera_matrix {
             matrix
         } else {
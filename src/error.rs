@@ -0,0 +1,107 @@
+//! Unified error of the fallible loading functions.
+
+use std::fmt;
+
+use crate::exec::FileError;
+use crate::input::RecordingError;
+use miniquad::ShaderError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// File could not be read or downloaded
+    File(FileError),
+    /// File is not an image of any supported format
+    ImageDecode(image::ImageError),
+    /// File is not a valid ttf/otf font
+    FontParse(&'static str),
+    /// Material shaders failed to compile or link
+    Shader(ShaderError),
+    /// Archive given to vfs is not a ZIP file or is not supported
+    InvalidArchive(&'static str),
+    /// Saved data could not be written, read or deleted
+    Storage { key: String, error: std::io::Error },
+    /// File is not a valid input recording
+    Recording(RecordingError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::File(err) => write!(f, "{}", err),
+            Error::ImageDecode(err) => write!(f, "Couldn't decode image: {}", err),
+            Error::FontParse(err) => write!(f, "Couldn't parse font: {}", err),
+            Error::Shader(err) => write!(f, "Couldn't build shader: {:?}", err),
+            Error::InvalidArchive(err) => write!(f, "Couldn't mount archive: {}", err),
+            Error::Storage { key, error } => write!(f, "Couldn't access saved {}: {}", key, error),
+            Error::Recording(err) => write!(f, "Couldn't load recording: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::File(err) => Some(err),
+            Error::ImageDecode(err) => Some(err),
+            Error::Storage { error, .. } => Some(error),
+            Error::Recording(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FileError> for Error {
+    fn from(err: FileError) -> Error {
+        Error::File(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Error {
+        Error::ImageDecode(err)
+    }
+}
+
+impl From<RecordingError> for Error {
+    fn from(err: RecordingError) -> Error {
+        Error::Recording(err)
+    }
+}
+
+impl From<ShaderError> for Error {
+    fn from(err: ShaderError) -> Error {
+        Error::Shader(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+    use crate::text::{try_load_ttf_font, try_load_ttf_font_from_bytes};
+    use crate::texture::{try_load_image, try_load_texture};
+
+    #[test]
+    fn fallible_loading() {
+        let mut runner = Runner::new(100., 100., async {
+            match try_load_texture("src/missing.png").await {
+                Err(Error::File(err)) => assert_eq!(err.path, "src/missing.png"),
+                _ => panic!("expected a file error"),
+            }
+            assert!(matches!(
+                try_load_image("Cargo.toml").await,
+                Err(Error::ImageDecode(_))
+            ));
+            assert!(matches!(
+                try_load_ttf_font_from_bytes(b"not a font"),
+                Err(Error::FontParse(_))
+            ));
+
+            assert!(try_load_image("src/font.png").await.is_ok());
+            assert!(try_load_ttf_font("src/ProggyClean.ttf").await.is_ok());
+        });
+
+        runner.run_frames(5);
+        assert!(runner.is_finished());
+    }
+}
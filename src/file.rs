@@ -2,8 +2,15 @@
 
 use crate::exec;
 
+pub(crate) mod data;
+pub mod vfs;
+mod zip;
+
+pub use crate::exec::FileError;
+pub use data::{delete_data, load_data, save_data, set_data_dir_name, use_memory_data_storage};
+
 /// Load file from the path and block until its loaded
-/// Will use filesystem on PC and do http request on web
+/// Will use filesystem on PC and do http request on web, through the "vfs" mounts if any
 pub fn load_file(path: &str) -> exec::FileLoadingFuture {
     use std::cell::RefCell;
     use std::rc::Rc;
@@ -11,14 +18,17 @@ pub fn load_file(path: &str) -> exec::FileLoadingFuture {
     let contents = Rc::new(RefCell::new(None));
     let path = path.to_owned();
 
+    crate::hot_reload::watch_file(&path);
+
     {
         let contents = contents.clone();
-        let err_path = path.clone();
 
-        miniquad::fs::load_file(&path, move |bytes| {
-            *contents.borrow_mut() =
-                Some(bytes.map_err(|kind| exec::FileError::new(kind, &err_path)));
-        });
+        vfs::load(
+            &path,
+            Rc::new(move |bytes| {
+                *contents.borrow_mut() = Some(bytes);
+            }),
+        );
     }
 
     exec::FileLoadingFuture { contents }
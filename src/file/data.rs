@@ -0,0 +1,233 @@
+//! Persistent key-value storage for save games and settings.
+//!
+//! Each key is a file in the game data directory:
+//! - Linux: `$XDG_DATA_HOME/<game>`, `~/.local/share/<game>` by default
+//! - macOS: `~/Library/Application Support/<game>`
+//! - Windows: `%APPDATA%\<game>`
+//!
+//! `<game>` is the executable name unless set with `set_data_dir_name`.
+//! On other platforms, and in headless mode, the data is stored in memory and lost on exit.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::{get_context, Error};
+
+pub(crate) enum DataStorage {
+    /// Name of the game directory or an absolute path, None for the executable name
+    Dir(Option<PathBuf>),
+    Memory(HashMap<String, Vec<u8>>),
+}
+
+impl DataStorage {
+    pub fn new(headless: bool) -> DataStorage {
+        if headless
+            || cfg!(any(
+                target_arch = "wasm32",
+                target_os = "android",
+                target_os = "ios"
+            ))
+        {
+            DataStorage::Memory(HashMap::new())
+        } else {
+            DataStorage::Dir(None)
+        }
+    }
+}
+
+fn user_data_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    if cfg!(target_os = "windows") {
+        var("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        var("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    }
+}
+
+fn default_dir_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem()?.to_str().map(str::to_string))
+        .unwrap_or_else(|| "macroquad".to_string())
+}
+
+fn storage_error(key: &str, error: std::io::Error) -> Error {
+    Error::Storage {
+        key: key.to_string(),
+        error,
+    }
+}
+
+/// Keys are used as file names, so only letters, digits, "-", "_" and "." are allowed
+fn check_key(key: &str) -> Result<(), Error> {
+    let valid = !key.is_empty()
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(storage_error(
+            key,
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid key"),
+        ))
+    }
+}
+
+/// Game data directory for the name given to set_data_dir_name
+fn data_dir(name: &Option<PathBuf>) -> Result<PathBuf, Error> {
+    let name = name
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(default_dir_name()));
+    if name.is_absolute() {
+        return Ok(name);
+    }
+
+    let user_dir = user_data_dir().ok_or_else(|| {
+        storage_error(
+            "",
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no user data directory"),
+        )
+    })?;
+
+    Ok(user_dir.join(name))
+}
+
+/// Name of the game directory in the user data directory, the executable name by default.
+/// An absolute path replaces the whole data directory.
+/// The name is kept even if there is no user data directory,
+/// then the data functions return `Error::Storage` for a relative name.
+pub fn set_data_dir_name(name: &str) {
+    let storage = &mut get_context().data_storage;
+
+    if let DataStorage::Dir(dir) = storage {
+        *dir = Some(PathBuf::from(name));
+    }
+}
+
+/// Keep the data in memory only, may be useful for tests
+pub fn use_memory_data_storage() {
+    get_context().data_storage = DataStorage::Memory(HashMap::new());
+}
+
+/// Store "data" under "key", replacing the previous data.
+/// The data is written to a temporary file, synced to disk and then renamed over the old one:
+/// after a crash or a power loss either the old data or the new one is kept, never a mix of them.
+pub fn save_data(key: &str, data: &[u8]) -> Result<(), Error> {
+    check_key(key)?;
+
+    let dir = match &mut get_context().data_storage {
+        DataStorage::Dir(name) => data_dir(name)?,
+        DataStorage::Memory(memory) => {
+            memory.insert(key.to_string(), data.to_vec());
+            return Ok(());
+        }
+    };
+
+    let write = || -> std::io::Result<()> {
+        std::fs::create_dir_all(&dir)?;
+
+        let temp = dir.join(format!(".{}.tmp", key));
+        let replace = || -> std::io::Result<()> {
+            let mut file = std::fs::File::create(&temp)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            std::fs::rename(&temp, dir.join(key))
+        };
+        if let Err(error) = replace() {
+            // nothing would ever clean up a half written file
+            let _ = std::fs::remove_file(&temp);
+            return Err(error);
+        }
+
+        // the rename itself is on disk only once the directory is synced
+        #[cfg(unix)]
+        std::fs::File::open(&dir)?.sync_all()?;
+
+        Ok(())
+    };
+
+    write().map_err(|error| storage_error(key, error))
+}
+
+/// Data stored under "key", None if there is none
+pub fn load_data(key: &str) -> Result<Option<Vec<u8>>, Error> {
+    check_key(key)?;
+
+    let dir = match &mut get_context().data_storage {
+        DataStorage::Dir(name) => data_dir(name)?,
+        DataStorage::Memory(memory) => return Ok(memory.get(key).cloned()),
+    };
+
+    match std::fs::read(dir.join(key)) {
+        Ok(data) => Ok(Some(data)),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(storage_error(key, error)),
+    }
+}
+
+/// Remove the data stored under "key", if any
+pub fn delete_data(key: &str) -> Result<(), Error> {
+    check_key(key)?;
+
+    let dir = match &mut get_context().data_storage {
+        DataStorage::Dir(name) => data_dir(name)?,
+        DataStorage::Memory(memory) => {
+            memory.remove(key);
+            return Ok(());
+        }
+    };
+
+    match std::fs::remove_file(dir.join(key)) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+            Err(storage_error(key, error))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+
+    #[test]
+    fn save_and_load() {
+        let mut runner = Runner::new(100., 100., async {
+            // headless mode uses the memory storage
+            assert_eq!(load_data("settings").unwrap(), None);
+            save_data("settings", b"volume=1").unwrap();
+            assert_eq!(load_data("settings").unwrap().unwrap(), b"volume=1");
+            delete_data("settings").unwrap();
+            assert_eq!(load_data("settings").unwrap(), None);
+            assert!(save_data("../settings", b"").is_err());
+
+            let dir = std::env::temp_dir().join(format!("macroquad_data_{}", std::process::id()));
+            get_context().data_storage = DataStorage::Dir(None);
+            set_data_dir_name(dir.to_str().unwrap());
+            save_data("save.1", b"first").unwrap();
+            save_data("save.1", b"second").unwrap();
+            assert_eq!(load_data("save.1").unwrap().unwrap(), b"second");
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+            delete_data("save.1").unwrap();
+            assert_eq!(load_data("save.1").unwrap(), None);
+
+            // a failed rename does not leave the temporary file behind
+            std::fs::create_dir_all(dir.join("save.2/taken")).unwrap();
+            assert!(save_data("save.2", b"data").is_err());
+            assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+
+        runner.step();
+        assert!(runner.is_finished());
+    }
+}
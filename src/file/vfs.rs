@@ -0,0 +1,251 @@
+//! Virtual filesystem: directories and ZIP archives mounted at path prefixes.
+//!
+//! ```ignore
+//! // "player.png" is "assets/player.png"
+//! vfs::mount_dir("", "assets");
+//! // files from the archive take precedence over the "assets" ones
+//! vfs::mount_zip_file("", "mods/hd_textures.zip").await?;
+//!
+//! let player = load_texture("player.png").await;
+//! ```
+//!
+//! Mounts are searched from the last one to the first, so later mounts overlay the earlier ones,
+//! and files missing in an overlay are still found in the mounts below it.
+//! Paths outside of all the mounts are loaded as they are, same as without any mounts.
+//! ZIP archives may be stored or deflated, ZIP64 and encryption are not supported.
+
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use super::zip::ZipArchive;
+use crate::exec::FileError;
+use crate::{get_context, Error};
+
+enum Source {
+    Dir(String),
+    Zip(Rc<ZipArchive>),
+}
+
+struct Mount {
+    prefix: String,
+    source: Source,
+}
+
+#[derive(Default)]
+pub(crate) struct Vfs {
+    mounts: Vec<Mount>,
+}
+
+#[derive(Clone)]
+enum Candidate {
+    Path(String),
+    Zip(Rc<ZipArchive>, String),
+}
+
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+
+    path.trim_end_matches('/').to_string()
+}
+
+impl Vfs {
+    fn mount(&mut self, prefix: &str, source: Source) {
+        self.mounts.push(Mount {
+            prefix: normalize(prefix),
+            source,
+        });
+    }
+
+    /// Where to look for the file, in order. Empty if the path is outside of all the mounts.
+    fn candidates(&self, path: &str) -> VecDeque<Candidate> {
+        let path = normalize(path);
+
+        self.mounts
+            .iter()
+            .rev()
+            .filter_map(|mount| {
+                let rest = if mount.prefix.is_empty() {
+                    path.as_str()
+                } else if path == mount.prefix {
+                    ""
+                } else {
+                    path.strip_prefix(&mount.prefix)?.strip_prefix('/')?
+                };
+
+                Some(match &mount.source {
+                    Source::Dir(dir) if dir.is_empty() => Candidate::Path(rest.to_string()),
+                    Source::Dir(dir) => Candidate::Path(format!("{}/{}", dir, rest)),
+                    Source::Zip(archive) => Candidate::Zip(archive.clone(), rest.to_string()),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Files under "prefix" are loaded from "dir": a directory on native platforms, an URL on web
+pub fn mount_dir(prefix: &str, dir: &str) {
+    let dir = normalize(dir);
+
+    get_context().vfs.mount(prefix, Source::Dir(dir));
+}
+
+/// Files under "prefix" are loaded from the ZIP archive
+pub fn mount_zip(prefix: &str, archive: Vec<u8>) -> Result<(), Error> {
+    let archive = ZipArchive::new(archive).map_err(Error::InvalidArchive)?;
+
+    get_context()
+        .vfs
+        .mount(prefix, Source::Zip(Rc::new(archive)));
+
+    Ok(())
+}
+
+/// Load the ZIP archive, through the already mounted ones, and mount it at "prefix"
+pub async fn mount_zip_file(prefix: &str, path: &str) -> Result<(), Error> {
+    let archive = super::load_file(path).await?;
+
+    mount_zip(prefix, archive)
+}
+
+/// Remove all the mounts at "prefix"
+pub fn unmount(prefix: &str) {
+    let prefix = normalize(prefix);
+
+    get_context()
+        .vfs
+        .mounts
+        .retain(|mount| mount.prefix != prefix);
+}
+
+/// Path of the file on the disk, None if it is in an archive or nowhere to be found
+#[cfg(not(any(target_arch = "wasm32", target_os = "android", target_os = "ios")))]
+pub(crate) fn native_path(path: &str) -> Option<String> {
+    let candidates = get_context().vfs.candidates(path);
+    if candidates.is_empty() {
+        return Some(path.to_string());
+    }
+
+    for candidate in candidates {
+        match candidate {
+            Candidate::Path(path) if std::path::Path::new(&path).exists() => return Some(path),
+            Candidate::Zip(archive, name) if archive.contains(&name) => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Load the file through the mounts, "on_loaded" is called exactly once
+pub(crate) fn load(path: &str, on_loaded: Rc<dyn Fn(Result<Vec<u8>, FileError>)>) {
+    let mut candidates = get_context().vfs.candidates(path);
+    if candidates.is_empty() {
+        candidates.push_back(Candidate::Path(path.to_string()));
+    }
+
+    load_candidates(path.to_string(), candidates, on_loaded);
+}
+
+/// Only a missing file falls through to the next mount, other errors are reported as is
+fn is_not_found(error: &miniquad::fs::Error) -> bool {
+    match error {
+        miniquad::fs::Error::IOError(err) => err.kind() == std::io::ErrorKind::NotFound,
+        // the web and mobile loaders don't tell a missing file from the other errors
+        _ => cfg!(any(
+            target_arch = "wasm32",
+            target_os = "android",
+            target_os = "ios"
+        )),
+    }
+}
+
+fn load_candidates(
+    path: String,
+    mut candidates: VecDeque<Candidate>,
+    on_loaded: Rc<dyn Fn(Result<Vec<u8>, FileError>)>,
+) {
+    let not_found = || {
+        let error = std::io::Error::new(std::io::ErrorKind::NotFound, "not found in any mount");
+
+        FileError::new(miniquad::fs::Error::IOError(error), &path)
+    };
+
+    match candidates.pop_front() {
+        Some(Candidate::Zip(archive, name)) => match archive.read(&name) {
+            Some(Ok(bytes)) => on_loaded(Ok(bytes)),
+            Some(Err(err)) => {
+                let error = std::io::Error::new(std::io::ErrorKind::InvalidData, err);
+
+                on_loaded(Err(FileError::new(
+                    miniquad::fs::Error::IOError(error),
+                    &path,
+                )))
+            }
+            None if candidates.is_empty() => on_loaded(Err(not_found())),
+            None => load_candidates(path, candidates, on_loaded),
+        },
+        Some(Candidate::Path(real_path)) => {
+            miniquad::fs::load_file(&real_path, move |bytes| match bytes {
+                Ok(bytes) => on_loaded(Ok(bytes)),
+                Err(kind) if candidates.is_empty() || !is_not_found(&kind) => {
+                    on_loaded(Err(FileError::new(kind, &path)))
+                }
+                Err(_) => load_candidates(path.clone(), candidates.clone(), on_loaded.clone()),
+            })
+        }
+        None => on_loaded(Err(not_found())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::load_file;
+    use crate::headless::Runner;
+
+    #[test]
+    fn mounts() {
+        let mut runner = Runner::new(100., 100., async {
+            mount_dir("assets", "src");
+            mount_zip("assets", crate::file::zip::tests::archive()).unwrap();
+            mount_zip("overlay", crate::file::zip::tests::archive()).unwrap();
+
+            // from the archive, overlaying "src"
+            assert_eq!(load_file("assets/a.txt").await.unwrap(), b"stored");
+            assert_eq!(load_file("./assets/dir/b.txt").await.unwrap(), b"deflated");
+            // not in the archive, so from "src"
+            let font = load_file("assets/font.png").await.unwrap();
+            assert_eq!(font, std::fs::read("src/font.png").unwrap());
+            // outside of the mounts
+            assert_eq!(load_file("src/font.png").await.unwrap(), font);
+
+            let err = load_file("overlay/missing.txt").await.unwrap_err();
+            assert_eq!(err.path, "overlay/missing.txt");
+            assert!(load_file("assets/missing.txt").await.is_err());
+
+            assert_eq!(native_path("assets/font.png").unwrap(), "src/font.png");
+            assert_eq!(native_path("assets/a.txt"), None);
+
+            unmount("assets");
+            assert!(load_file("assets/a.txt").await.is_err());
+
+            // an error other than a missing file is not hidden by the mounts below
+            let dir = std::env::temp_dir().join(format!("macroquad_vfs_{}", std::process::id()));
+            std::fs::create_dir_all(dir.join("upper/data.txt")).unwrap();
+            std::fs::create_dir_all(dir.join("lower")).unwrap();
+            std::fs::write(dir.join("lower/data.txt"), b"lower").unwrap();
+            mount_dir("layered", dir.join("lower").to_str().unwrap());
+            assert_eq!(load_file("layered/data.txt").await.unwrap(), b"lower");
+            mount_dir("layered", dir.join("upper").to_str().unwrap());
+            assert!(load_file("layered/data.txt").await.is_err());
+            unmount("layered");
+            std::fs::remove_dir_all(&dir).unwrap();
+        });
+
+        runner.run_frames(20);
+        assert!(runner.is_finished());
+    }
+}
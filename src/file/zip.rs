@@ -0,0 +1,221 @@
+//! Minimal ZIP reader for vfs: stored and deflated entries, no ZIP64, no encryption.
+
+use std::collections::HashMap;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+struct Entry {
+    method: u16,
+    /// Start of the entry data in the archive
+    offset: usize,
+    compressed_size: usize,
+    size: usize,
+}
+
+pub(crate) struct ZipArchive {
+    data: Vec<u8>,
+    entries: HashMap<String, Entry>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset.checked_add(2)?)?;
+
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Inflate raw deflate data, stopping as soon as the output grows past "size",
+/// so a corrupted or malicious entry can't take all the memory
+fn inflate_limited(data: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let mut stream = inflate::InflateStream::new();
+    let mut bytes = vec![];
+
+    let mut read = 0;
+    loop {
+        let (consumed, output) = stream.update(&data[read..])?;
+        if output.is_empty() {
+            return Ok(bytes);
+        }
+        if bytes.len() + output.len() > size {
+            return Err("corrupted archive entry".to_string());
+        }
+        read += consumed;
+        bytes.extend_from_slice(output);
+    }
+}
+
+impl ZipArchive {
+    pub fn new(data: Vec<u8>) -> Result<ZipArchive, &'static str> {
+        let entries = Self::read_entries(&data).ok_or("not a valid ZIP archive")?;
+
+        Ok(ZipArchive { data, entries })
+    }
+
+    fn read_entries(data: &[u8]) -> Option<HashMap<String, Entry>> {
+        // the end of central directory record is 22 bytes followed by up to 64k of comment
+        let min_end = data.len().saturating_sub(22 + u16::MAX as usize);
+        let end = (min_end..=data.len().checked_sub(22)?)
+            .rev()
+            .find(|offset| u32_at(data, *offset) == Some(END_OF_CENTRAL_DIRECTORY))?;
+
+        let count = u16_at(data, end + 10)? as usize;
+        let mut header = u32_at(data, end + 16)? as usize;
+
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            if u32_at(data, header)? != CENTRAL_DIRECTORY_HEADER {
+                return None;
+            }
+            let method = u16_at(data, header + 10)?;
+            let compressed_size = u32_at(data, header + 20)? as usize;
+            let size = u32_at(data, header + 24)? as usize;
+            let name_len = u16_at(data, header + 28)? as usize;
+            let extra_len = u16_at(data, header + 30)? as usize;
+            let comment_len = u16_at(data, header + 32)? as usize;
+            let local_header = u32_at(data, header + 42)? as usize;
+            let name = data.get(header + 46..header + 46 + name_len)?;
+            let name = String::from_utf8_lossy(name).replace('\\', "/");
+
+            // the local header may have a different extra field than the central one
+            if u32_at(data, local_header)? != LOCAL_FILE_HEADER {
+                return None;
+            }
+            // sizes and offsets come from the archive, so overflows are treated as corruption
+            let offset = local_header
+                .checked_add(30)?
+                .checked_add(u16_at(data, local_header + 26)? as usize)?
+                .checked_add(u16_at(data, local_header + 28)? as usize)?;
+            data.get(offset..offset.checked_add(compressed_size)?)?;
+
+            if !name.ends_with('/') {
+                entries.insert(
+                    name,
+                    Entry {
+                        method,
+                        offset,
+                        compressed_size,
+                        size,
+                    },
+                );
+            }
+            header = header.checked_add(46 + name_len + extra_len + comment_len)?;
+        }
+
+        Some(entries)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    /// None if there is no such file in the archive
+    pub fn read(&self, name: &str) -> Option<Result<Vec<u8>, String>> {
+        let entry = self.entries.get(name)?;
+        let data = &self.data[entry.offset..entry.offset + entry.compressed_size];
+
+        let bytes = match entry.method {
+            STORED => Ok(data.to_vec()),
+            DEFLATED => inflate_limited(data, entry.size),
+            method => Err(format!("unsupported compression method {}", method)),
+        };
+
+        Some(bytes.and_then(|bytes| {
+            if bytes.len() == entry.size {
+                Ok(bytes)
+            } else {
+                Err("corrupted archive entry".to_string())
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Archive with "a.txt" stored and "dir/b.txt" deflated, built by hand
+    pub(crate) fn archive() -> Vec<u8> {
+        let files: [(&str, u16, &[u8], &[u8]); 2] = [
+            ("a.txt", STORED, b"stored", b"stored"),
+            // raw deflate stream of "deflated"
+            (
+                "dir/b.txt",
+                DEFLATED,
+                b"deflated",
+                &[75, 73, 77, 203, 73, 44, 73, 77, 1, 0],
+            ),
+        ];
+
+        let mut data = vec![];
+        let mut central = vec![];
+        for (name, method, contents, compressed) in files.iter() {
+            let offset = data.len() as u32;
+
+            data.extend_from_slice(&LOCAL_FILE_HEADER.to_le_bytes());
+            data.extend_from_slice(&[20, 0, 0, 0]);
+            data.extend_from_slice(&method.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0, 0]);
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(compressed);
+
+            central.extend_from_slice(&CENTRAL_DIRECTORY_HEADER.to_le_bytes());
+            central.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+            central.extend_from_slice(&method.to_le_bytes());
+            central.extend_from_slice(&[0; 8]);
+            central.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+            central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            central.extend_from_slice(&[0; 12]);
+            central.extend_from_slice(&offset.to_le_bytes());
+            central.extend_from_slice(name.as_bytes());
+        }
+
+        let central_offset = data.len() as u32;
+        data.extend_from_slice(&central);
+        data.extend_from_slice(&END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 2, 0, 2, 0]);
+        data.extend_from_slice(&(central.len() as u32).to_le_bytes());
+        data.extend_from_slice(&central_offset.to_le_bytes());
+        data.extend_from_slice(&[0, 0]);
+
+        data
+    }
+
+    #[test]
+    fn read_zip() {
+        let archive = ZipArchive::new(archive()).unwrap();
+
+        assert_eq!(archive.read("a.txt").unwrap().unwrap(), b"stored");
+        assert_eq!(archive.read("dir/b.txt").unwrap().unwrap(), b"deflated");
+        assert!(archive.read("b.txt").is_none());
+        assert!(inflate_limited(&[75, 73, 77, 203, 73, 44, 73, 77, 1, 0], 7).is_err());
+        assert!(ZipArchive::new(b"not a zip".to_vec()).is_err());
+    }
+
+    #[test]
+    fn corrupted_sizes() {
+        let data = archive();
+        let central = u32_at(&data, data.len() - 6).unwrap() as usize;
+
+        // compressed size and local header offset of the first entry
+        for field in [20, 42].iter() {
+            let mut data = data.clone();
+            data[central + field..central + field + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+            assert!(ZipArchive::new(data).is_err());
+        }
+    }
+}
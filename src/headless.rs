@@ -0,0 +1,238 @@
+//! Running macroquad programs without a window and a GPU, for tests and CI.
+//!
+//! ```ignore
+//! #[test]
+//! fn space_makes_player_jump() {
+//!     let jumped = Rc::new(Cell::new(false));
+//!
+//!     let mut runner = Runner::new(800., 600., game(jumped.clone()));
+//!     runner.run_frames(10);
+//!     runner.push_event(InputEvent::KeyDown(KeyCode::Space, KeyMods::default(), false));
+//!     runner.step();
+//!
+//!     assert!(jumped.get());
+//! }
+//! ```
+//!
+//! Everything works as usual, except for the things that need a real GPU:
+//! render targets, `get_internal_gl` and raw miniquad calls are not available.
+//! Textures and materials are kept in CPU memory, draw calls are processed by `QuadGl::draw_headless`.
+
+use std::future::Future;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::input::InputEvent;
+use crate::texture::Image;
+use crate::{Context, CONTEXT, MAIN_FUTURE};
+
+pub use quad_gl::capture::{self, DrawCallRecord};
+
+/// macroquad state is global, so only one runner may exist at a time.
+/// Runners from tests running in parallel wait for each other on this lock.
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Owner of the global macroquad state, stepping the main future frame by frame
+pub struct Runner {
+    frame_time: f64,
+    events: Vec<InputEvent>,
+    finished: bool,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl Runner {
+    pub const DEFAULT_FRAME_TIME: f64 = 1. / 60.;
+
+    /// Blocks while any other runner exists.
+    /// The future is not polled until the first "step".
+    pub fn new(
+        screen_width: f32,
+        screen_height: f32,
+        future: impl Future<Output = ()> + 'static,
+    ) -> Runner {
+        // a panicked test poisons the lock, but the state is reset by the next runner anyway
+        let lock = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        unsafe {
+            CONTEXT = Some(Context::headless(screen_width, screen_height));
+            crate::get_context()
+                .draw_context
+                .gl
+                .capture_draw_calls(true);
+            MAIN_FUTURE = Some(Box::pin(future));
+        }
+
+        Runner {
+            frame_time: Self::DEFAULT_FRAME_TIME,
+            events: vec![],
+            finished: false,
+            _lock: lock,
+        }
+    }
+
+    /// Frame time for all the following frames, `DEFAULT_FRAME_TIME` by default
+    pub fn set_frame_time(&mut self, frame_time: f64) {
+        self.frame_time = frame_time;
+    }
+
+    /// Queue the event to be delivered right before the next frame,
+    /// the same way window events are delivered
+    pub fn push_event(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    /// Run one frame, returns false once the main future is finished
+    pub fn step(&mut self) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        for event in self.events.drain(..) {
+            crate::live_input_event(event);
+        }
+
+        if crate::run_frame() {
+            self.finished = true;
+            return false;
+        }
+        crate::advance_time(self.frame_time);
+
+        true
+    }
+
+    /// Run up to "frames" frames, returns false if the main future finished on the way
+    pub fn run_frames(&mut self, frames: usize) -> bool {
+        (0..frames).all(|_| self.step())
+    }
+
+    /// Draw the screen with the CPU rasterizer, so "get_screen_data" and "screenshot" work.
+    /// Off by default, rasterizing every frame is slow.
+    pub fn enable_rasterizer(&mut self) {
+        let context = crate::get_context();
+
+        context
+            .draw_context
+            .gl
+            .enable_rasterizer(context.screen_width as u16, context.screen_height as u16);
+    }
+
+    /// Screen at the end of the last frame, with the first row at the top,
+    /// as in image files. Requires "enable_rasterizer".
+    pub fn screenshot(&self) -> Image {
+        crate::texture::screen_image()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Draw calls of the last frame, see `capture` for comparing them with golden files
+    pub fn draw_calls(&self) -> &[DrawCallRecord] {
+        &crate::get_context().draw_calls
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        // the main future may still reference the context, so it goes first
+        unsafe {
+            MAIN_FUTURE = None;
+            CONTEXT = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{is_key_down, is_key_pressed, KeyCode, KeyMods};
+    use crate::time::get_time;
+    use crate::window::next_frame;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn steps_main_future() {
+        let frames = Rc::new(Cell::new(0));
+        let presses = Rc::new(Cell::new(0));
+
+        let mut runner = Runner::new(320., 240., {
+            let frames = frames.clone();
+            let presses = presses.clone();
+            async move {
+                for _ in 0..10 {
+                    if is_key_pressed(KeyCode::Space) {
+                        presses.set(presses.get() + 1);
+                    }
+                    crate::shapes::draw_rectangle(10., 10., 20., 20., quad_gl::RED);
+                    crate::text::draw_text("headless", 10., 40., 20., quad_gl::WHITE);
+
+                    frames.set(frames.get() + 1);
+                    next_frame().await
+                }
+            }
+        });
+        runner.set_frame_time(0.5);
+
+        assert!(runner.step());
+        let first_frames = frames.get();
+        assert!(runner.run_frames(3));
+        assert_eq!(frames.get(), first_frames + 3);
+        assert_eq!(get_time(), 2.);
+
+        runner.push_event(InputEvent::KeyDown(
+            KeyCode::Space,
+            KeyMods::default(),
+            false,
+        ));
+        runner.step();
+        runner.step();
+        assert_eq!(presses.get(), 1);
+        assert!(is_key_down(KeyCode::Space));
+
+        assert!(!runner.run_frames(100));
+        assert!(runner.is_finished());
+        assert_eq!(frames.get(), 10);
+    }
+
+    #[test]
+    fn captures_draw_calls() {
+        let mut runner = Runner::new(320., 240., async {
+            crate::shapes::draw_rectangle(10., 10., 20., 20., quad_gl::RED);
+            next_frame().await;
+            loop {
+                next_frame().await
+            }
+        });
+
+        runner.step();
+        assert_eq!(runner.draw_calls().len(), 1);
+        assert_eq!(runner.draw_calls()[0].vertices.len(), 4);
+        assert_eq!(
+            runner.draw_calls()[0].vertices[0].position(),
+            [10., 10., 0.]
+        );
+
+        // only the last frame is kept
+        runner.step();
+        assert!(runner.draw_calls().is_empty());
+    }
+
+    #[test]
+    fn rasterizes_screen() {
+        let mut runner = Runner::new(320., 240., async {
+            loop {
+                crate::shapes::draw_rectangle(10., 10., 20., 20., quad_gl::RED);
+                next_frame().await
+            }
+        });
+        runner.enable_rasterizer();
+        runner.step();
+
+        let screenshot = runner.screenshot();
+        let red: [u8; 4] = quad_gl::RED.into();
+        assert_eq!(screenshot.get_image_data()[15 * 320 + 15], red);
+        assert_eq!(screenshot.get_image_data()[0], [0, 0, 0, 255]);
+        assert_eq!(screenshot.get_image_data()[224 * 320 + 15], [0, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,407 @@
+//! Reloading textures, materials and fonts when their files change, for development.
+//!
+//! ```ignore
+//! #[macroquad::main("Game")]
+//! async fn main() {
+//!     #[cfg(debug_assertions)]
+//!     hot_reload::enable();
+//!
+//!     let player = load_texture("player.png").await;
+//!     ...
+//! }
+//! ```
+//!
+//! Once enabled, files loaded through `file::load_file` are watched by polling their modification time.
+//! Files from vfs archives are not watched.
+//! Textures, fonts and materials loaded from the changed files are updated in place,
+//! so their handles stay valid.
+//! Only native platforms are supported, on web and mobile nothing is ever reloaded.
+
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::get_context;
+use crate::material::Material;
+use crate::text::Font;
+use crate::texture::{Image, Texture2D};
+use miniquad::{PipelineParams, UniformType};
+
+/// Seconds between checks of the files modification times
+const POLL_INTERVAL: f64 = 0.5;
+
+#[derive(Clone)]
+enum Watched {
+    Texture(Texture2D),
+    Font(Font),
+    Material {
+        material: Material,
+        vertex_path: String,
+        fragment_path: String,
+        pipeline_params: PipelineParams,
+        uniforms: Vec<(String, UniformType)>,
+    },
+}
+
+#[derive(Default)]
+struct WatchedFile {
+    modified: Option<SystemTime>,
+    assets: Vec<Watched>,
+}
+
+pub(crate) struct HotReload {
+    files: HashMap<String, WatchedFile>,
+    last_poll: f64,
+}
+
+/// Start watching the files loaded from now on
+pub fn enable() {
+    let context = get_context();
+
+    if context.hot_reload.is_none() {
+        context.hot_reload = Some(HotReload {
+            files: HashMap::new(),
+            last_poll: miniquad::date::now(),
+        });
+    }
+}
+
+/// Stop watching all the files
+pub fn disable() {
+    get_context().hot_reload = None;
+}
+
+pub fn is_enabled() -> bool {
+    get_context().hot_reload.is_some()
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android", target_os = "ios")))]
+    {
+        let path = crate::file::vfs::native_path(path)?;
+
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    #[cfg(any(target_arch = "wasm32", target_os = "android", target_os = "ios"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+fn read(path: &str) -> Option<Vec<u8>> {
+    #[cfg(not(any(target_arch = "wasm32", target_os = "android", target_os = "ios")))]
+    {
+        std::fs::read(crate::file::vfs::native_path(path)?).ok()
+    }
+
+    #[cfg(any(target_arch = "wasm32", target_os = "android", target_os = "ios"))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Called by file::load_file
+pub(crate) fn watch_file(path: &str) {
+    if let Some(hot_reload) = get_context().hot_reload.as_mut() {
+        hot_reload
+            .files
+            .entry(path.to_string())
+            .or_default()
+            .modified = modified(path);
+    }
+}
+
+fn watch(path: &str, asset: Watched) {
+    if let Some(hot_reload) = get_context().hot_reload.as_mut() {
+        hot_reload
+            .files
+            .entry(path.to_string())
+            .or_default()
+            .assets
+            .push(asset);
+    }
+}
+
+pub(crate) fn watch_texture(path: &str, texture: Texture2D) {
+    watch(path, Watched::Texture(texture));
+}
+
+pub(crate) fn watch_font(path: &str, font: Font) {
+    watch(path, Watched::Font(font));
+}
+
+pub(crate) fn watch_material(
+    vertex_path: &str,
+    fragment_path: &str,
+    material: Material,
+    pipeline_params: PipelineParams,
+    uniforms: Vec<(String, UniformType)>,
+) {
+    let asset = Watched::Material {
+        material,
+        vertex_path: vertex_path.to_string(),
+        fragment_path: fragment_path.to_string(),
+        pipeline_params,
+        uniforms,
+    };
+
+    watch(vertex_path, asset.clone());
+    watch(fragment_path, asset);
+}
+
+/// Reload the assets of the changed files, called once per frame
+pub(crate) fn update() {
+    let changed = match get_context().hot_reload.as_mut() {
+        Some(hot_reload) => {
+            let now = miniquad::date::now();
+            if now - hot_reload.last_poll < POLL_INTERVAL {
+                return;
+            }
+            hot_reload.last_poll = now;
+
+            let mut changed = vec![];
+            for (path, file) in &mut hot_reload.files {
+                let modified = modified(path);
+                if modified != file.modified {
+                    file.modified = modified;
+                    changed.push((path.clone(), file.assets.clone()));
+                }
+            }
+            changed
+        }
+        None => return,
+    };
+
+    for (path, assets) in changed {
+        for asset in assets {
+            if let Err(err) = reload(&path, asset) {
+                #[cfg(feature = "log-impl")]
+                crate::logging::warn!("Hot reload of {} failed: {}", path, err);
+                #[cfg(not(feature = "log-impl"))]
+                eprintln!("Hot reload of {} failed: {}", path, err);
+            }
+        }
+    }
+}
+
+fn reload(path: &str, asset: Watched) -> Result<(), String> {
+    let read_file = |path: &str| read(path).ok_or_else(|| format!("Couldn't read {}", path));
+
+    match asset {
+        Watched::Texture(texture) => {
+            let image = Image::try_from_file_with_format(&read_file(path)?, None)
+                .map_err(|e| e.to_string())?;
+
+            if image.width as f32 != texture.width() || image.height as f32 != texture.height() {
+                return Err("texture size changed, restart to see the new one".to_string());
+            }
+            crate::texture::update_texture(texture, &image);
+        }
+        Watched::Font(font) => {
+            crate::text::reload_font(font, &read_file(path)?).map_err(|e| e.to_string())?;
+        }
+        Watched::Material {
+            material,
+            vertex_path,
+            fragment_path,
+            pipeline_params,
+            uniforms,
+        } => {
+            let to_string = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|e| e.to_string());
+            let vertex_shader = to_string(read_file(&vertex_path)?)?;
+            let fragment_shader = to_string(read_file(&fragment_path)?)?;
+
+            material
+                .rebuild(&vertex_shader, &fragment_shader, pipeline_params, uniforms)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+    use quad_gl::Color;
+
+    #[test]
+    fn reload_texture() {
+        let path = std::env::temp_dir().join(format!("hot_reload_{}.png", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let save = |color| {
+            let image = Image::gen_image_color(2, 2, color);
+            image::save_buffer(&path, &image.bytes, 2, 2, image::ColorType::RGBA(8)).unwrap();
+        };
+        save(Color::new(1., 0., 0., 1.));
+
+        let texture = std::rc::Rc::new(std::cell::Cell::new(None));
+        let mut runner = Runner::new(100., 100., {
+            let path = path.clone();
+            let texture = texture.clone();
+            async move {
+                enable();
+                texture.set(Some(crate::texture::load_texture(&path).await));
+                loop {
+                    crate::window::next_frame().await
+                }
+            }
+        });
+        runner.run_frames(2);
+        let texture = texture.get().unwrap();
+        let pixel = || crate::texture::get_texture_data(texture).get_image_data()[0];
+        assert_eq!(pixel(), [255, 0, 0, 255]);
+
+        save(Color::new(0., 0., 1., 1.));
+        force_poll(&path);
+
+        runner.step();
+        assert_eq!(pixel(), [0, 0, 255, 255]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// File systems may store mtimes with a whole second precision, so forget the known one
+    fn force_poll(path: &str) {
+        let hot_reload = get_context().hot_reload.as_mut().unwrap();
+        hot_reload.files.get_mut(path).unwrap().modified = None;
+        hot_reload.last_poll = 0.;
+    }
+
+    #[test]
+    fn reload_font() {
+        let path = std::env::temp_dir().join(format!("hot_reload_{}.ttf", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        std::fs::write(&path, include_bytes!("ProggyClean.ttf")).unwrap();
+        let dancing = std::fs::read("examples/DancingScriptRegular.ttf").unwrap();
+
+        let font = std::rc::Rc::new(std::cell::Cell::new(None));
+        let mut runner = Runner::new(100., 100., {
+            let path = path.clone();
+            let font = font.clone();
+            async move {
+                enable();
+                font.set(Some(crate::text::load_ttf_font(&path).await));
+                loop {
+                    crate::window::next_frame().await
+                }
+            }
+        });
+        runner.run_frames(2);
+        let font = font.get().unwrap();
+        let texture = font.texture();
+        let measure = |font| crate::text::measure_text("Hot reload", Some(font), 15, 1.);
+        let proggy = measure(font);
+        let atlas = crate::texture::get_texture_data(texture).bytes;
+
+        std::fs::write(&path, &dancing).unwrap();
+        force_poll(&path);
+        runner.step();
+
+        // the same font and atlas texture, with the cached glyphs of the new font
+        assert_eq!(font.texture(), texture);
+        assert_ne!(crate::texture::get_texture_data(texture).bytes, atlas);
+        let reloaded = measure(font);
+        assert_ne!(reloaded, proggy);
+        assert_eq!(
+            reloaded,
+            measure(crate::text::load_ttf_font_from_bytes(&dancing))
+        );
+        crate::text::draw_text_ex(
+            "Hot reload",
+            0.,
+            20.,
+            crate::text::TextParams {
+                font,
+                ..Default::default()
+            },
+        );
+
+        // a broken file keeps the last good font
+        std::fs::write(&path, b"not a font").unwrap();
+        force_poll(&path);
+        runner.step();
+        assert_eq!(measure(font), reloaded);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn reload_material() {
+        let dir = std::env::temp_dir().join(format!("hot_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let vertex_path = dir.join("material.vert").to_str().unwrap().to_string();
+        let fragment_path = dir.join("material.frag").to_str().unwrap().to_string();
+        std::fs::write(&vertex_path, "void main() {}").unwrap();
+        std::fs::write(&fragment_path, "void main() {}").unwrap();
+
+        let material = std::rc::Rc::new(std::cell::Cell::new(None));
+        let mut runner = Runner::new(100., 100., {
+            let (vertex_path, fragment_path) = (vertex_path.clone(), fragment_path.clone());
+            let material = material.clone();
+            async move {
+                enable();
+                let params = crate::material::MaterialParams {
+                    uniforms: vec![("Time".to_string(), UniformType::Float1)],
+                    ..Default::default()
+                };
+                material.set(Some(
+                    crate::material::load_material_from_files(&vertex_path, &fragment_path, params)
+                        .await
+                        .unwrap(),
+                ));
+                loop {
+                    if let Some(material) = material.get() {
+                        crate::material::gl_use_material(material);
+                        crate::shapes::draw_rectangle(0., 0., 10., 10., quad_gl::RED);
+                        crate::material::gl_use_default_material();
+                    }
+                    crate::window::next_frame().await
+                }
+            }
+        });
+        runner.run_frames(2);
+        let material = material.get().unwrap();
+        material.set_uniform("Time", 1.0f32);
+        let pipeline = runner.draw_calls()[0].pipeline;
+
+        // changes of either shader file rebuild the material
+        for path in [&vertex_path, &fragment_path].iter() {
+            let assets = &get_context().hot_reload.as_ref().unwrap().files[*path].assets;
+            assert!(matches!(assets[..], [Watched::Material { material: m, .. }] if m == material));
+        }
+        let asset =
+            get_context().hot_reload.as_ref().unwrap().files[&fragment_path].assets[0].clone();
+
+        std::fs::write(
+            &fragment_path,
+            "uniform sampler2D _ScreenTexture; void main() {}",
+        )
+        .unwrap();
+        force_poll(&fragment_path);
+        runner.step();
+        assert_eq!(
+            get_context().hot_reload.as_ref().unwrap().files[&fragment_path].modified,
+            modified(&fragment_path)
+        );
+        assert!(reload(&fragment_path, asset.clone()).is_ok());
+        // the handle stays valid and keeps drawing with the same pipeline
+        material.set_uniform("Time", 2.0f32);
+        assert_eq!(runner.draw_calls()[0].pipeline, pipeline);
+
+        // broken or missing shader files leave the material as it was
+        std::fs::write(&vertex_path, [0xff, 0xfe]).unwrap();
+        assert!(reload(&vertex_path, asset.clone()).is_err());
+        std::fs::remove_file(&fragment_path).unwrap();
+        assert!(reload(&vertex_path, asset).is_err());
+        runner.step();
+        assert_eq!(runner.draw_calls()[0].pipeline, pipeline);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,7 +1,28 @@
-//! Cross-platform mouse, keyboard (and gamepads soon) module. 
+//! Cross-platform mouse, keyboard and gamepads module, with action mapping on top of them.
 
-pub use miniquad::{KeyCode, MouseButton};
 use crate::get_context;
+use glam::Vec2;
+pub use miniquad::{CursorIcon, KeyCode, KeyMods, MouseButton};
+
+mod actions;
+pub(crate) mod gamepad;
+pub(crate) mod names;
+pub(crate) mod replay;
+
+pub use actions::{
+    action_axis, bind_action, bind_axis, get_input_map, is_action_down, is_action_pressed,
+    is_action_released, set_input_map, AxisBinding, Binding, InputMap, InputMapError,
+};
+pub use replay::{
+    is_recording, is_replaying, start_recording, start_replay, stop_recording, stop_replay,
+    InputEvent, Recording, RecordingError,
+};
+
+pub use gamepad::{
+    gamepad_axis, gamepad_name, gamepads, get_gamepad_events, is_gamepad_button_down,
+    is_gamepad_button_pressed, is_gamepad_button_released, is_gamepad_connected,
+    set_gamepad_deadzone, GamepadAxis, GamepadButton, GamepadEvent, GamepadId, VirtualGamepad,
+};
 
 pub fn mouse_position() -> (f32, f32) {
     let context = get_context();
@@ -9,6 +30,54 @@ pub fn mouse_position() -> (f32, f32) {
     (context.mouse_position.x(), context.mouse_position.y())
 }
 
+/// Mouse movement since the last frame, in window pixels
+pub fn mouse_delta() -> (f32, f32) {
+    let context = get_context();
+
+    let delta = context
+        .last_mouse_position
+        .map_or(Vec2::new(0., 0.), |last| context.mouse_position - last);
+    (delta.x(), delta.y())
+}
+
+/// Raw, unaccelerated mouse movement since the last frame.
+/// Keeps working when the cursor is grabbed and stuck at the window border,
+/// so this is the one to use for first-person cameras.
+/// Units are platform dependent, so some sensitivity setting is usually needed.
+pub fn mouse_raw_delta() -> (f32, f32) {
+    let context = get_context();
+
+    (context.mouse_raw_delta.x(), context.mouse_raw_delta.y())
+}
+
+/// Lock the cursor inside the window
+pub fn set_cursor_grab(grab: bool) {
+    let context = get_context();
+
+    if let Some(ctx) = context.quad_context.as_mut() {
+        ctx.set_cursor_grab(grab);
+    }
+}
+
+/// Show or hide the cursor while it is over the window
+pub fn show_mouse(shown: bool) {
+    let context = get_context();
+
+    if let Some(ctx) = context.quad_context.as_mut() {
+        ctx.show_mouse(shown);
+    }
+}
+
+/// Change the system cursor icon, like a text cursor for text fields
+/// or a resize cursor for splitters
+pub fn set_mouse_cursor(cursor_icon: CursorIcon) {
+    let context = get_context();
+
+    if let Some(ctx) = context.quad_context.as_mut() {
+        ctx.set_mouse_cursor(cursor_icon);
+    }
+}
+
 pub fn mouse_wheel() -> (f32, f32) {
     let context = get_context();
 
@@ -29,6 +98,72 @@ pub fn is_key_down(key_code: KeyCode) -> bool {
     context.keys_down.contains(&key_code)
 }
 
+/// Detect if the key has been released this frame
+pub fn is_key_released(key_code: KeyCode) -> bool {
+    let context = get_context();
+
+    context.keys_released.contains(&key_code)
+}
+
+/// Current state of keyboard modifiers: shift, ctrl, alt and logo
+pub fn get_key_mods() -> KeyMods {
+    let context = get_context();
+
+    context.key_mods
+}
+
+pub fn is_shift_down() -> bool {
+    get_key_mods().shift
+}
+
+pub fn is_ctrl_down() -> bool {
+    get_key_mods().ctrl
+}
+
+pub fn is_alt_down() -> bool {
+    get_key_mods().alt
+}
+
+/// Detect if "windows" key on PC or "command" key on mac is being pressed
+pub fn is_logo_down() -> bool {
+    get_key_mods().logo
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEventKind {
+    Down,
+    Up,
+}
+
+/// Single key press or release, with modifiers active at the moment of the event
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyEvent {
+    pub key_code: KeyCode,
+    pub mods: KeyMods,
+    /// Key down generated by OS keyboard auto-repeat
+    pub repeat: bool,
+    pub kind: KeyEventKind,
+}
+
+/// All key events from the last frame, in the order they happened.
+///
+/// ```ignore
+/// for event in get_key_events() {
+///     if event.kind == KeyEventKind::Down
+///         && event.key_code == KeyCode::S
+///         && event.mods.ctrl
+///         && event.mods.shift
+///     {
+///         save_as();
+///     }
+/// }
+/// ```
+pub fn get_key_events() -> Vec<KeyEvent> {
+    let context = get_context();
+
+    context.key_events.clone()
+}
+
 /// Return the last pressed char.
 /// Each "get_char_pressed" call will consume a character from the input queue.
 pub fn get_char_pressed() -> Option<char> {
@@ -40,8 +175,13 @@ pub fn get_char_pressed() -> Option<char> {
 /// Return the last pressed key.
 pub fn get_last_key_pressed() -> Option<KeyCode> {
     let context = get_context();
-    // TODO: this will return a random key from keys_pressed HashMap instead of the last one, fix me later
-    context.keys_pressed.iter().next().cloned()
+
+    context
+        .key_events
+        .iter()
+        .rev()
+        .find(|event| event.kind == KeyEventKind::Down && !event.repeat)
+        .map(|event| event.key_code)
 }
 
 /// Detect if the key is being pressed
@@ -65,4 +205,377 @@ pub fn is_mouse_button_released(btn: MouseButton) -> bool {
     context.mouse_released.contains(&btn)
 }
 
+/// Touch id used for touches simulated with the mouse, see `simulate_touch_with_mouse`
+pub const MOUSE_TOUCH_ID: u64 = u64::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchPhase {
+    /// Finger touched the screen during the last frame
+    Started,
+    /// Finger is on the screen and did not move during the last frame
+    Stationary,
+    /// Finger moved during the last frame
+    Moved,
+    /// Finger left the screen during the last frame
+    Ended,
+    /// System cancelled the touch during the last frame, treat it like `Ended`
+    /// but without any "release" actions
+    Cancelled,
+}
+
+impl From<miniquad::TouchPhase> for TouchPhase {
+    fn from(phase: miniquad::TouchPhase) -> TouchPhase {
+        match phase {
+            miniquad::TouchPhase::Started => TouchPhase::Started,
+            miniquad::TouchPhase::Moved => TouchPhase::Moved,
+            miniquad::TouchPhase::Ended => TouchPhase::Ended,
+            miniquad::TouchPhase::Cancelled => TouchPhase::Cancelled,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Touch {
+    /// Stable for the whole lifetime of the touch, from `Started` to `Ended`
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub position: Vec2,
+}
+
+/// All the touches active during the last frame.
+/// Touches ended during the frame are included, with `Ended` or `Cancelled` phase.
+/// A touch that started and ended during the same frame is reported as `Started`,
+/// and as `Ended` or `Cancelled` on the next frame.
+pub fn touches() -> Vec<Touch> {
+    let context = get_context();
+
+    let mut touches = context.touches.values().cloned().collect::<Vec<_>>();
+    touches.sort_by_key(|touch| touch.id);
+    touches
+}
+
+/// Report the first finger on the screen as the left mouse button,
+/// so mouse-driven code works on touch screens. Enabled by default.
+pub fn simulate_mouse_with_touch(option: bool) {
+    let context = get_context();
+
+    context.simulate_mouse_with_touch = option;
+}
+
+/// Report the left mouse button as a touch with `MOUSE_TOUCH_ID` id,
+/// useful for testing touch controls on desktop. Disabled by default.
+pub fn simulate_touch_with_mouse(option: bool) {
+    let context = get_context();
+
+    context.simulate_touch_with_mouse = option;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+    use crate::window::next_frame;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Runner calling "observe" every frame, the result of the last frame is kept
+    fn observe<T: 'static>(observe: fn() -> T) -> (Runner, Rc<RefCell<Option<T>>>) {
+        let last = Rc::new(RefCell::new(None));
+        let runner = Runner::new(100., 100., {
+            let last = last.clone();
+            async move {
+                loop {
+                    *last.borrow_mut() = Some(observe());
+                    next_frame().await
+                }
+            }
+        });
+        (runner, last)
+    }
+
+    fn touch(phase: TouchPhase, id: u64, x: f32, y: f32) -> InputEvent {
+        InputEvent::Touch(phase, id, x, y)
+    }
+
+    fn phases(touches: &[Touch]) -> Vec<(u64, TouchPhase)> {
+        touches
+            .iter()
+            .map(|touch| (touch.id, touch.phase))
+            .collect()
+    }
+
+    #[test]
+    fn touch_phases() {
+        let (mut runner, last) = observe(touches);
+        let phases =
+            |last: &Rc<RefCell<Option<Vec<Touch>>>>| phases(last.borrow().as_ref().unwrap());
+
+        runner.push_event(touch(TouchPhase::Started, 1, 10., 10.));
+        runner.push_event(touch(TouchPhase::Moved, 1, 15., 10.));
+        runner.step();
+        assert_eq!(phases(&last), [(1, TouchPhase::Started)]);
+        assert_eq!(
+            last.borrow().as_ref().unwrap()[0].position,
+            Vec2::new(15., 10.)
+        );
+
+        runner.step();
+        assert_eq!(phases(&last), [(1, TouchPhase::Stationary)]);
+
+        runner.push_event(touch(TouchPhase::Moved, 1, 20., 10.));
+        runner.push_event(touch(TouchPhase::Started, 2, 50., 50.));
+        runner.step();
+        assert_eq!(
+            phases(&last),
+            [(1, TouchPhase::Moved), (2, TouchPhase::Started)]
+        );
+
+        runner.push_event(touch(TouchPhase::Ended, 1, 20., 10.));
+        runner.push_event(touch(TouchPhase::Cancelled, 2, 50., 50.));
+        runner.step();
+        assert_eq!(
+            phases(&last),
+            [(1, TouchPhase::Ended), (2, TouchPhase::Cancelled)]
+        );
+
+        runner.step();
+        assert_eq!(phases(&last), []);
+    }
+
+    #[test]
+    fn touch_started_and_ended_in_one_frame() {
+        let (mut runner, last) = observe(touches);
+
+        runner.push_event(touch(TouchPhase::Started, 1, 10., 10.));
+        runner.push_event(touch(TouchPhase::Ended, 1, 10., 10.));
+        runner.step();
+        assert_eq!(
+            phases(last.borrow().as_ref().unwrap()),
+            [(1, TouchPhase::Started)]
+        );
+
+        runner.step();
+        assert_eq!(
+            phases(last.borrow().as_ref().unwrap()),
+            [(1, TouchPhase::Ended)]
+        );
+
+        runner.step();
+        assert_eq!(phases(last.borrow().as_ref().unwrap()), []);
+    }
+
+    #[test]
+    fn mouse_from_primary_touch() {
+        let (mut runner, last) = observe(|| {
+            (
+                mouse_position(),
+                is_mouse_button_pressed(MouseButton::Left),
+                is_mouse_button_down(MouseButton::Left),
+                is_mouse_button_released(MouseButton::Left),
+            )
+        });
+        let mouse = |last: &Rc<RefCell<Option<_>>>| last.borrow().unwrap();
+
+        runner.push_event(touch(TouchPhase::Started, 1, 10., 10.));
+        runner.step();
+        assert_eq!(mouse(&last), ((10., 10.), true, true, false));
+
+        // only the primary touch moves the mouse
+        runner.push_event(touch(TouchPhase::Started, 2, 50., 50.));
+        runner.push_event(touch(TouchPhase::Moved, 1, 20., 10.));
+        runner.step();
+        assert_eq!(mouse(&last), ((20., 10.), false, true, false));
+
+        runner.push_event(touch(TouchPhase::Ended, 1, 25., 10.));
+        runner.step();
+        assert_eq!(mouse(&last), ((25., 10.), false, false, true));
+
+        // the touch still on the screen does not become primary
+        runner.push_event(touch(TouchPhase::Moved, 2, 60., 50.));
+        runner.step();
+        assert_eq!(mouse(&last), ((25., 10.), false, false, false));
+
+        // the next started touch does
+        runner.push_event(touch(TouchPhase::Started, 3, 70., 70.));
+        runner.step();
+        assert_eq!(mouse(&last), ((70., 70.), true, true, false));
+
+        runner.push_event(touch(TouchPhase::Cancelled, 3, 70., 70.));
+        runner.step();
+        assert_eq!(mouse(&last), ((70., 70.), false, false, true));
+
+        simulate_mouse_with_touch(false);
+        runner.push_event(touch(TouchPhase::Started, 4, 90., 90.));
+        runner.step();
+        assert_eq!(mouse(&last), ((70., 70.), false, false, false));
+    }
 
+    #[test]
+    fn mouse_deltas() {
+        let (mut runner, last) = observe(|| (mouse_delta(), mouse_raw_delta()));
+        let deltas = |last: &Rc<RefCell<Option<_>>>| last.borrow().unwrap();
+
+        runner.step();
+        assert_eq!(deltas(&last), ((0., 0.), (0., 0.)));
+
+        // no delta for the first known position
+        runner.push_event(InputEvent::MouseMotion(10., 20.));
+        runner.step();
+        assert_eq!(deltas(&last), ((0., 0.), (0., 0.)));
+
+        // accumulated over the frame
+        runner.push_event(InputEvent::MouseMotion(12., 25.));
+        runner.push_event(InputEvent::MouseMotion(15., 22.));
+        runner.push_event(InputEvent::RawMouseMotion(1., -2.));
+        runner.push_event(InputEvent::RawMouseMotion(3., 0.5));
+        runner.step();
+        assert_eq!(deltas(&last), ((5., 2.), (4., -1.5)));
+
+        // and reset at the end of the frame
+        runner.step();
+        assert_eq!(deltas(&last), ((0., 0.), (0., 0.)));
+    }
+
+    #[test]
+    fn mouse_delta_from_touch() {
+        let (mut runner, last) = observe(mouse_delta);
+
+        runner.push_event(InputEvent::MouseMotion(10., 10.));
+        runner.step();
+
+        // a new touch does not jump from the last mouse position
+        runner.push_event(touch(TouchPhase::Started, 1, 50., 60.));
+        runner.step();
+        assert_eq!(last.borrow().unwrap(), (0., 0.));
+
+        runner.push_event(touch(TouchPhase::Moved, 1, 55., 58.));
+        runner.step();
+        assert_eq!(last.borrow().unwrap(), (5., -2.));
+
+        runner.step();
+        assert_eq!(last.borrow().unwrap(), (0., 0.));
+    }
+
+    const SHIFT: KeyMods = KeyMods {
+        shift: true,
+        ctrl: false,
+        alt: false,
+        logo: false,
+    };
+    const SHIFT_CTRL: KeyMods = KeyMods {
+        shift: true,
+        ctrl: true,
+        alt: false,
+        logo: false,
+    };
+
+    fn key_down(key_code: KeyCode, mods: KeyMods, repeat: bool) -> InputEvent {
+        InputEvent::KeyDown(key_code, mods, repeat)
+    }
+
+    fn key_up(key_code: KeyCode, mods: KeyMods) -> InputEvent {
+        InputEvent::KeyUp(key_code, mods)
+    }
+
+    #[test]
+    fn key_states() {
+        let (mut runner, last) = observe(|| {
+            (
+                is_key_pressed(KeyCode::A),
+                is_key_down(KeyCode::A),
+                is_key_released(KeyCode::A),
+                get_last_key_pressed(),
+            )
+        });
+        let state = |last: &Rc<RefCell<Option<_>>>| last.borrow().unwrap();
+
+        runner.step();
+        assert_eq!(state(&last), (false, false, false, None));
+
+        runner.push_event(key_down(KeyCode::A, KeyMods::default(), false));
+        runner.push_event(key_down(KeyCode::B, KeyMods::default(), false));
+        runner.step();
+        assert_eq!(state(&last), (true, true, false, Some(KeyCode::B)));
+
+        // auto-repeat is not a press
+        runner.push_event(key_down(KeyCode::A, KeyMods::default(), true));
+        runner.step();
+        assert_eq!(state(&last), (false, true, false, None));
+
+        runner.push_event(key_up(KeyCode::A, KeyMods::default()));
+        runner.step();
+        assert_eq!(state(&last), (false, false, true, None));
+
+        runner.step();
+        assert_eq!(state(&last), (false, false, false, None));
+
+        // pressed and released within one frame
+        runner.push_event(key_down(KeyCode::A, KeyMods::default(), false));
+        runner.push_event(key_up(KeyCode::A, KeyMods::default()));
+        runner.step();
+        assert_eq!(state(&last), (true, false, true, Some(KeyCode::A)));
+    }
+
+    #[test]
+    fn key_mods() {
+        let (mut runner, last) = observe(get_key_mods);
+        let mods = |last: &Rc<RefCell<Option<KeyMods>>>| last.borrow().unwrap();
+
+        runner.push_event(key_down(KeyCode::LeftShift, SHIFT, false));
+        runner.push_event(key_down(KeyCode::LeftControl, SHIFT_CTRL, false));
+        runner.step();
+        assert_eq!(mods(&last), SHIFT_CTRL);
+
+        // the platform still reports ctrl in its own key up
+        runner.push_event(key_up(KeyCode::LeftControl, SHIFT_CTRL));
+        runner.step();
+        assert_eq!(mods(&last), SHIFT);
+
+        // but shift is still active while the other shift is held
+        runner.push_event(key_down(KeyCode::RightShift, SHIFT, false));
+        runner.push_event(key_up(KeyCode::LeftShift, SHIFT));
+        runner.step();
+        assert_eq!(mods(&last), SHIFT);
+
+        runner.push_event(key_up(KeyCode::RightShift, SHIFT));
+        runner.step();
+        assert_eq!(mods(&last), KeyMods::default());
+
+        // mods are kept between frames
+        runner.step();
+        assert_eq!(mods(&last), KeyMods::default());
+    }
+
+    #[test]
+    fn key_events_order() {
+        let (mut runner, last) = observe(get_key_events);
+
+        runner.push_event(key_down(KeyCode::LeftControl, SHIFT_CTRL, false));
+        runner.push_event(key_down(KeyCode::S, SHIFT_CTRL, false));
+        runner.push_event(key_down(KeyCode::S, SHIFT_CTRL, true));
+        runner.push_event(key_up(KeyCode::S, SHIFT_CTRL));
+        runner.push_event(key_up(KeyCode::LeftControl, SHIFT_CTRL));
+        runner.step();
+
+        let event = |key_code, mods, repeat, kind| KeyEvent {
+            key_code,
+            mods,
+            repeat,
+            kind,
+        };
+        assert_eq!(
+            last.borrow().as_ref().unwrap(),
+            &[
+                event(KeyCode::LeftControl, SHIFT_CTRL, false, KeyEventKind::Down),
+                event(KeyCode::S, SHIFT_CTRL, false, KeyEventKind::Down),
+                event(KeyCode::S, SHIFT_CTRL, true, KeyEventKind::Down),
+                event(KeyCode::S, SHIFT_CTRL, false, KeyEventKind::Up),
+                event(KeyCode::LeftControl, SHIFT, false, KeyEventKind::Up),
+            ]
+        );
+
+        runner.step();
+        assert!(last.borrow().as_ref().unwrap().is_empty());
+    }
+}
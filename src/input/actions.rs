@@ -0,0 +1,465 @@
+//! Named actions and axes on top of raw keys, mouse buttons and gamepads.
+//!
+//! ```ignore
+//! bind_action("jump", Binding::Key(KeyCode::Space)).unwrap();
+//! bind_action("jump", Binding::Gamepad(GamepadButton::South)).unwrap();
+//! bind_axis("move_x", AxisBinding::Keys { negative: KeyCode::A, positive: KeyCode::D }).unwrap();
+//! bind_axis("move_x", AxisBinding::GamepadAxis(GamepadAxis::LeftX)).unwrap();
+//!
+//! loop {
+//!     if is_action_pressed("jump") {
+//!         player.jump();
+//!     }
+//!     player.x += action_axis("move_x") * speed;
+//!
+//!     next_frame().await
+//! }
+//! ```
+
+use std::fmt;
+
+use super::names::{parse_gamepad_axis, parse_gamepad_button, parse_key_code, parse_mouse_button};
+use super::{GamepadAxis, GamepadButton, KeyCode, MouseButton};
+use crate::get_context;
+
+/// Source of a digital action
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// Button on any of the connected gamepads
+    Gamepad(GamepadButton),
+}
+
+/// Source of an analog axis, in [-1..1] range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisBinding {
+    /// -1 while "negative" is down, 1 while "positive" is down
+    Keys {
+        negative: KeyCode,
+        positive: KeyCode,
+    },
+    /// -1 while "negative" is down, 1 while "positive" is down, on any of the connected gamepads
+    GamepadButtons {
+        negative: GamepadButton,
+        positive: GamepadButton,
+    },
+    /// Analog axis on any of the connected gamepads, with the deadzone applied
+    GamepadAxis(GamepadAxis),
+}
+
+#[derive(Debug)]
+pub struct InputMapError {
+    /// Line of the loaded text, 0 for errors not coming from `InputMap::load`
+    pub line: usize,
+    pub msg: String,
+}
+
+impl std::error::Error for InputMapError {}
+
+impl fmt::Display for InputMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "Input map error: {}", self.msg)
+        } else {
+            write!(f, "Input map error at line {}: {}", self.line, self.msg)
+        }
+    }
+}
+
+/// Set of named actions and axes with their bindings.
+///
+/// Can be saved to and loaded from a simple text format, one action or axis per line:
+/// ```text
+/// # comments are allowed
+/// action jump: key Space, mouse Left, gamepad South
+/// axis move_x: keys A D, gamepad_buttons DPadLeft DPadRight, gamepad_axis LeftX
+/// ```
+/// Action and axis names should not contain whitespaces or ':'.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputMap {
+    actions: Vec<(String, Vec<Binding>)>,
+    axes: Vec<(String, Vec<AxisBinding>)>,
+}
+
+fn entry<'a, T>(
+    entries: &'a mut Vec<(String, Vec<T>)>,
+    name: &str,
+) -> Result<&'a mut Vec<T>, InputMapError> {
+    if name.is_empty() || name.contains(|c: char| c == ':' || c.is_whitespace()) {
+        return Err(InputMapError {
+            line: 0,
+            msg: format!(
+                "invalid name {:?}, names should not contain whitespaces or ':'",
+                name
+            ),
+        });
+    }
+
+    let ix = match entries.iter().position(|(entry, _)| entry == name) {
+        Some(ix) => ix,
+        None => {
+            entries.push((name.to_string(), vec![]));
+            entries.len() - 1
+        }
+    };
+    Ok(&mut entries[ix].1)
+}
+
+fn bindings<'a, T>(entries: &'a [(String, Vec<T>)], name: &str) -> &'a [T] {
+    entries
+        .iter()
+        .find(|(entry, _)| entry == name)
+        .map_or(&[], |(_, bindings)| &bindings[..])
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        InputMap::default()
+    }
+
+    /// Add one more binding to the action. Action is triggered by any of its bindings.
+    /// Fails if the name is empty or contains whitespaces or ':'.
+    pub fn bind_action(&mut self, action: &str, binding: Binding) -> Result<(), InputMapError> {
+        let bindings = entry(&mut self.actions, action)?;
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        Ok(())
+    }
+
+    /// Add one more binding to the axis.
+    /// Axis value is the value of the binding deflected the most.
+    /// Fails if the name is empty or contains whitespaces or ':'.
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) -> Result<(), InputMapError> {
+        let bindings = entry(&mut self.axes, axis)?;
+
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        Ok(())
+    }
+
+    /// Remove all the bindings of the action
+    pub fn unbind_action(&mut self, action: &str) {
+        self.actions.retain(|(name, _)| name != action);
+    }
+
+    /// Remove all the bindings of the axis
+    pub fn unbind_axis(&mut self, axis: &str) {
+        self.axes.retain(|(name, _)| name != axis);
+    }
+
+    pub fn action_bindings(&self, action: &str) -> &[Binding] {
+        bindings(&self.actions, action)
+    }
+
+    pub fn axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        bindings(&self.axes, axis)
+    }
+
+    /// Serialize the map into the text format described in `InputMap` docs
+    pub fn save(&self) -> String {
+        let mut res = String::new();
+
+        for (action, bindings) in &self.actions {
+            let bindings = bindings
+                .iter()
+                .map(|binding| match binding {
+                    Binding::Key(key) => format!("key {:?}", key),
+                    Binding::Mouse(button) => format!("mouse {:?}", button),
+                    Binding::Gamepad(button) => format!("gamepad {:?}", button),
+                })
+                .collect::<Vec<_>>();
+            res.push_str(&format!("action {}: {}\n", action, bindings.join(", ")));
+        }
+
+        for (axis, bindings) in &self.axes {
+            let bindings = bindings
+                .iter()
+                .map(|binding| match binding {
+                    AxisBinding::Keys { negative, positive } => {
+                        format!("keys {:?} {:?}", negative, positive)
+                    }
+                    AxisBinding::GamepadButtons { negative, positive } => {
+                        format!("gamepad_buttons {:?} {:?}", negative, positive)
+                    }
+                    AxisBinding::GamepadAxis(axis) => format!("gamepad_axis {:?}", axis),
+                })
+                .collect::<Vec<_>>();
+            res.push_str(&format!("axis {}: {}\n", axis, bindings.join(", ")));
+        }
+
+        res
+    }
+
+    /// Parse the map from the text format described in `InputMap` docs
+    pub fn load(data: &str) -> Result<InputMap, InputMapError> {
+        let mut map = InputMap::new();
+
+        for (n, line) in data.lines().enumerate() {
+            let error = |msg: String| InputMapError { line: n + 1, msg };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let colon = line
+                .find(':')
+                .ok_or_else(|| error("expected ':' after the name".to_string()))?;
+            let (header, bindings) = (&line[..colon], &line[colon + 1..]);
+            let header = header.split_whitespace().collect::<Vec<_>>();
+
+            match &header[..] {
+                ["action", action] => {
+                    // actions without bindings are still preserved
+                    entry(&mut map.actions, action).map_err(|err| error(err.msg))?;
+
+                    for binding in bindings.split(',').filter(|b| !b.trim().is_empty()) {
+                        let binding = parse_binding(binding).map_err(error)?;
+                        map.bind_action(action, binding)
+                            .map_err(|err| error(err.msg))?;
+                    }
+                }
+                ["axis", axis] => {
+                    entry(&mut map.axes, axis).map_err(|err| error(err.msg))?;
+
+                    for binding in bindings.split(',').filter(|b| !b.trim().is_empty()) {
+                        let binding = parse_axis_binding(binding).map_err(error)?;
+                        map.bind_axis(axis, binding).map_err(|err| error(err.msg))?;
+                    }
+                }
+                _ => {
+                    return Err(error(format!(
+                        "expected \"action <name>\" or \"axis <name>\", got {:?}",
+                        &line[..colon]
+                    )))
+                }
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+fn parse<T>(parse_fn: fn(&str) -> Option<T>, name: &str) -> Result<T, String> {
+    parse_fn(name).ok_or_else(|| format!("unknown input name {:?}", name))
+}
+
+fn parse_binding(binding: &str) -> Result<Binding, String> {
+    let tokens = binding.split_whitespace().collect::<Vec<_>>();
+
+    match &tokens[..] {
+        ["key", key] => Ok(Binding::Key(parse(parse_key_code, key)?)),
+        ["mouse", button] => Ok(Binding::Mouse(parse(parse_mouse_button, button)?)),
+        ["gamepad", button] => Ok(Binding::Gamepad(parse(parse_gamepad_button, button)?)),
+        _ => Err(format!("invalid action binding {:?}", binding.trim())),
+    }
+}
+
+fn parse_axis_binding(binding: &str) -> Result<AxisBinding, String> {
+    let tokens = binding.split_whitespace().collect::<Vec<_>>();
+
+    match &tokens[..] {
+        ["keys", negative, positive] => Ok(AxisBinding::Keys {
+            negative: parse(parse_key_code, negative)?,
+            positive: parse(parse_key_code, positive)?,
+        }),
+        ["gamepad_buttons", negative, positive] => Ok(AxisBinding::GamepadButtons {
+            negative: parse(parse_gamepad_button, negative)?,
+            positive: parse(parse_gamepad_button, positive)?,
+        }),
+        ["gamepad_axis", axis] => Ok(AxisBinding::GamepadAxis(parse(parse_gamepad_axis, axis)?)),
+        _ => Err(format!("invalid axis binding {:?}", binding.trim())),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Query {
+    Down,
+    Pressed,
+    Released,
+}
+
+fn gamepad_button(button: GamepadButton, query: Query) -> bool {
+    let gamepads = &get_context().gamepads;
+
+    gamepads.ids().any(|id| match query {
+        Query::Down => gamepads.is_down(id, button),
+        Query::Pressed => gamepads.is_pressed(id, button),
+        Query::Released => gamepads.is_released(id, button),
+    })
+}
+
+fn binding(binding: Binding, query: Query) -> bool {
+    let context = get_context();
+
+    match (binding, query) {
+        (Binding::Key(key), Query::Down) => context.keys_down.contains(&key),
+        (Binding::Key(key), Query::Pressed) => context.keys_pressed.contains(&key),
+        (Binding::Key(key), Query::Released) => context.keys_released.contains(&key),
+        (Binding::Mouse(button), Query::Down) => context.mouse_down.contains(&button),
+        (Binding::Mouse(button), Query::Pressed) => context.mouse_pressed.contains(&button),
+        (Binding::Mouse(button), Query::Released) => context.mouse_released.contains(&button),
+        (Binding::Gamepad(button), query) => gamepad_button(button, query),
+    }
+}
+
+fn axis_binding(binding: AxisBinding) -> f32 {
+    let context = get_context();
+
+    let buttons = |negative: bool, positive: bool| positive as i32 as f32 - negative as i32 as f32;
+
+    match binding {
+        AxisBinding::Keys { negative, positive } => buttons(
+            context.keys_down.contains(&negative),
+            context.keys_down.contains(&positive),
+        ),
+        AxisBinding::GamepadButtons { negative, positive } => buttons(
+            gamepad_button(negative, Query::Down),
+            gamepad_button(positive, Query::Down),
+        ),
+        AxisBinding::GamepadAxis(axis) => context
+            .gamepads
+            .ids()
+            .map(|id| context.gamepads.axis(id, axis))
+            .fold(0., |a: f32, b: f32| if b.abs() > a.abs() { b } else { a }),
+    }
+}
+
+fn action(action: &str, query: Query) -> bool {
+    get_context()
+        .input_map
+        .action_bindings(action)
+        .iter()
+        .any(|b| binding(*b, query))
+}
+
+/// Replace the current input map
+pub fn set_input_map(map: InputMap) {
+    get_context().input_map = map;
+}
+
+/// Copy of the current input map, useful for rebinding menus
+pub fn get_input_map() -> InputMap {
+    get_context().input_map.clone()
+}
+
+/// Add a binding to the action in the current input map, see `InputMap::bind_action`
+pub fn bind_action(action: &str, binding: Binding) -> Result<(), InputMapError> {
+    get_context().input_map.bind_action(action, binding)
+}
+
+/// Add a binding to the axis in the current input map, see `InputMap::bind_axis`
+pub fn bind_axis(axis: &str, binding: AxisBinding) -> Result<(), InputMapError> {
+    get_context().input_map.bind_axis(axis, binding)
+}
+
+/// Detect if any binding of the action is being pressed
+pub fn is_action_down(name: &str) -> bool {
+    action(name, Query::Down)
+}
+
+/// Detect if any binding of the action has been pressed this frame
+pub fn is_action_pressed(name: &str) -> bool {
+    action(name, Query::Pressed)
+}
+
+/// Detect if any binding of the action has been released this frame
+pub fn is_action_released(name: &str) -> bool {
+    action(name, Query::Released)
+}
+
+/// Axis value in [-1..1] range, 0 for axes without bindings
+pub fn action_axis(name: &str) -> f32 {
+    get_context()
+        .input_map
+        .axis_bindings(name)
+        .iter()
+        .map(|b| axis_binding(*b))
+        .fold(0., |a: f32, b: f32| if b.abs() > a.abs() { b } else { a })
+        .clamp(-1., 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load() {
+        let mut map = InputMap::new();
+        map.bind_action("jump", Binding::Key(KeyCode::Space))
+            .unwrap();
+        map.bind_action("jump", Binding::Mouse(MouseButton::Left))
+            .unwrap();
+        map.bind_action("jump", Binding::Gamepad(GamepadButton::South))
+            .unwrap();
+        map.bind_action("jump", Binding::Key(KeyCode::Space))
+            .unwrap();
+        map.bind_axis(
+            "move_x",
+            AxisBinding::Keys {
+                negative: KeyCode::A,
+                positive: KeyCode::D,
+            },
+        )
+        .unwrap();
+        map.bind_axis(
+            "move_x",
+            AxisBinding::GamepadButtons {
+                negative: GamepadButton::DPadLeft,
+                positive: GamepadButton::DPadRight,
+            },
+        )
+        .unwrap();
+        map.bind_axis("move_x", AxisBinding::GamepadAxis(GamepadAxis::LeftX))
+            .unwrap();
+
+        let saved = map.save();
+        assert_eq!(
+            saved,
+            "action jump: key Space, mouse Left, gamepad South\n\
+             axis move_x: keys A D, gamepad_buttons DPadLeft DPadRight, gamepad_axis LeftX\n"
+        );
+        assert_eq!(InputMap::load(&saved).unwrap(), map);
+
+        map.unbind_action("jump");
+        assert!(map.action_bindings("jump").is_empty());
+    }
+
+    #[test]
+    fn load_errors() {
+        let map = InputMap::load("# rebinds\n\naction fire:\n  action use: key E  \n").unwrap();
+        assert!(map.action_bindings("fire").is_empty());
+        assert_eq!(map.action_bindings("use"), &[Binding::Key(KeyCode::E)]);
+
+        assert_eq!(InputMap::load("action jump key Space").unwrap_err().line, 1);
+        assert_eq!(
+            InputMap::load("\naction jump: key Spacebar")
+                .unwrap_err()
+                .line,
+            2
+        );
+        assert!(InputMap::load("axis move: keys A").is_err());
+        assert!(InputMap::load("button jump: key Space").is_err());
+    }
+
+    #[test]
+    fn invalid_names() {
+        let mut map = InputMap::new();
+        let binding = Binding::Key(KeyCode::Space);
+        let axis_binding = AxisBinding::GamepadAxis(GamepadAxis::LeftX);
+
+        assert!(map.bind_action("", binding).is_err());
+        assert!(map.bind_action("jump high", binding).is_err());
+        assert!(map.bind_action("jump:", binding).is_err());
+        assert!(map.bind_axis("move\tx", axis_binding).is_err());
+        assert_eq!(map, InputMap::new());
+
+        let err = map.bind_axis("move:x", axis_binding).unwrap_err();
+        assert_eq!(err.line, 0);
+        assert!(err.to_string().starts_with("Input map error: invalid name"));
+    }
+}
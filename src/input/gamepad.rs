@@ -0,0 +1,820 @@
+//! Gamepads state, platform backends and virtual gamepads.
+
+use std::{cell::RefCell, collections::HashMap, collections::HashSet, rc::Rc};
+
+use crate::get_context;
+
+/// Stable gamepad identifier.
+/// A gamepad that disconnects and connects again gets the same id back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(usize);
+
+/// Buttons, named after their position on the gamepad, xbox-style.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    /// A on xbox, cross on playstation
+    South,
+    /// B on xbox, circle on playstation
+    East,
+    /// Y on xbox, triangle on playstation
+    North,
+    /// X on xbox, square on playstation
+    West,
+    LeftShoulder,
+    RightShoulder,
+    /// Digital state of the left trigger, analog state is `GamepadAxis::LeftTrigger`
+    LeftTrigger,
+    /// Digital state of the right trigger, analog state is `GamepadAxis::RightTrigger`
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Analog axes. Sticks are in [-1..1] range, with positive Y pointing down,
+/// triggers are in [0..1] range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    const COUNT: usize = 6;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+}
+
+/// Event reported by a gamepad backend.
+/// "device" is a backend-specific string, stable between reconnects of the same device.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum RawGamepadEvent {
+    Connected {
+        device: String,
+        name: String,
+    },
+    Disconnected {
+        device: String,
+    },
+    Button {
+        device: String,
+        button: GamepadButton,
+        down: bool,
+    },
+    Axis {
+        device: String,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+pub(crate) trait GamepadBackend {
+    fn poll(&mut self, events: &mut Vec<RawGamepadEvent>);
+}
+
+type VirtualEvents = Rc<RefCell<Vec<RawGamepadEvent>>>;
+
+struct VirtualBackend {
+    events: VirtualEvents,
+}
+
+impl GamepadBackend for VirtualBackend {
+    fn poll(&mut self, events: &mut Vec<RawGamepadEvent>) {
+        events.extend(self.events.borrow_mut().drain(..));
+    }
+}
+
+struct GamepadState {
+    name: String,
+    connected: bool,
+    down: HashSet<GamepadButton>,
+    pressed: HashSet<GamepadButton>,
+    released: HashSet<GamepadButton>,
+    axes: [f32; GamepadAxis::COUNT],
+}
+
+pub(crate) struct Gamepads {
+    backends: Vec<Box<dyn GamepadBackend>>,
+    virtual_events: VirtualEvents,
+    virtual_count: usize,
+    ids: HashMap<String, GamepadId>,
+    gamepads: Vec<GamepadState>,
+    events: Vec<GamepadEvent>,
+    deadzones: [f32; GamepadAxis::COUNT],
+    raw_events: Vec<RawGamepadEvent>,
+}
+
+impl Gamepads {
+    pub const DEFAULT_DEADZONE: f32 = 0.1;
+
+    /// Gamepads with no platform backend, only virtual gamepads will work.
+    pub fn new() -> Gamepads {
+        let virtual_events = VirtualEvents::default();
+
+        Gamepads {
+            backends: vec![Box::new(VirtualBackend {
+                events: virtual_events.clone(),
+            })],
+            virtual_events,
+            virtual_count: 0,
+            ids: HashMap::new(),
+            gamepads: vec![],
+            events: vec![],
+            deadzones: [Self::DEFAULT_DEADZONE; GamepadAxis::COUNT],
+            raw_events: vec![],
+        }
+    }
+
+    /// Gamepads with the best backend available on current platform.
+    pub fn with_platform_backend() -> Gamepads {
+        #[allow(unused_mut)]
+        let mut gamepads = Gamepads::new();
+
+        #[cfg(target_os = "linux")]
+        gamepads.backends.push(Box::new(evdev::EvdevBackend::new()));
+
+        gamepads
+    }
+
+    /// Poll all the backends, should be called once at the beginning of the frame.
+    pub fn update(&mut self) {
+        let mut raw_events = std::mem::take(&mut self.raw_events);
+
+        for backend in &mut self.backends {
+            backend.poll(&mut raw_events);
+        }
+        for event in raw_events.drain(..) {
+            self.handle_event(event);
+        }
+
+        self.raw_events = raw_events;
+    }
+
+    pub fn end_frame(&mut self) {
+        for gamepad in &mut self.gamepads {
+            gamepad.pressed.clear();
+            gamepad.released.clear();
+        }
+        self.events.clear();
+    }
+
+    fn handle_event(&mut self, event: RawGamepadEvent) {
+        match event {
+            RawGamepadEvent::Connected { device, name } => {
+                let id = match self.ids.get(&device) {
+                    Some(id) => *id,
+                    None => {
+                        let id = GamepadId(self.gamepads.len());
+                        self.ids.insert(device, id);
+                        self.gamepads.push(GamepadState {
+                            name: String::new(),
+                            connected: false,
+                            down: HashSet::new(),
+                            pressed: HashSet::new(),
+                            released: HashSet::new(),
+                            axes: [0.; GamepadAxis::COUNT],
+                        });
+                        id
+                    }
+                };
+                let gamepad = &mut self.gamepads[id.0];
+                if !gamepad.connected {
+                    gamepad.name = name;
+                    gamepad.connected = true;
+                    self.events.push(GamepadEvent::Connected(id));
+                }
+            }
+            RawGamepadEvent::Disconnected { device } => {
+                if let Some(gamepad) = self.gamepad_by_device(&device) {
+                    let released = gamepad.down.drain().collect::<Vec<_>>();
+                    gamepad.released.extend(released);
+                    gamepad.axes = [0.; GamepadAxis::COUNT];
+                    gamepad.connected = false;
+
+                    let id = self.ids[&device];
+                    self.events.push(GamepadEvent::Disconnected(id));
+                }
+            }
+            RawGamepadEvent::Button {
+                device,
+                button,
+                down,
+            } => {
+                if let Some(gamepad) = self.gamepad_by_device(&device) {
+                    if down {
+                        if gamepad.down.insert(button) {
+                            gamepad.pressed.insert(button);
+                        }
+                    } else if gamepad.down.remove(&button) {
+                        gamepad.released.insert(button);
+                    }
+                }
+            }
+            RawGamepadEvent::Axis {
+                device,
+                axis,
+                value,
+            } => {
+                if let Some(gamepad) = self.gamepad_by_device(&device) {
+                    gamepad.axes[axis.index()] = value;
+                }
+            }
+        }
+    }
+
+    fn gamepad_by_device(&mut self, device: &str) -> Option<&mut GamepadState> {
+        let id = self.ids.get(device)?;
+        let gamepad = &mut self.gamepads[id.0];
+
+        if gamepad.connected {
+            Some(gamepad)
+        } else {
+            None
+        }
+    }
+
+    fn get(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.gamepads.get(id.0).filter(|gamepad| gamepad.connected)
+    }
+
+    pub fn connected(&self) -> Vec<GamepadId> {
+        (0..self.gamepads.len())
+            .map(GamepadId)
+            .filter(|id| self.get(*id).is_some())
+            .collect()
+    }
+
+    /// All the gamepads ever seen, including the disconnected ones
+    pub fn ids(&self) -> impl Iterator<Item = GamepadId> {
+        (0..self.gamepads.len()).map(GamepadId)
+    }
+
+    pub fn is_down(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.get(id)
+            .map_or(false, |gamepad| gamepad.down.contains(&button))
+    }
+
+    pub fn is_pressed(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.get(id)
+            .map_or(false, |gamepad| gamepad.pressed.contains(&button))
+    }
+
+    pub fn is_released(&self, id: GamepadId, button: GamepadButton) -> bool {
+        self.gamepads
+            .get(id.0)
+            .map_or(false, |gamepad| gamepad.released.contains(&button))
+    }
+
+    pub fn axis(&self, id: GamepadId, axis: GamepadAxis) -> f32 {
+        let value = self
+            .get(id)
+            .map_or(0., |gamepad| gamepad.axes[axis.index()]);
+        let deadzone = self.deadzones[axis.index()];
+
+        if value.abs() <= deadzone {
+            return 0.;
+        }
+        // rescale the rest of the range, so the value still goes smoothly from 0 to 1
+        value.signum() * ((value.abs() - deadzone) / (1. - deadzone)).min(1.)
+    }
+
+    pub fn set_deadzone(&mut self, axis: GamepadAxis, deadzone: f32) {
+        self.deadzones[axis.index()] = deadzone.clamp(0., 0.99);
+    }
+
+    fn connect_virtual(&mut self, name: &str) -> VirtualGamepad {
+        let device = format!("virtual-{}", self.virtual_count);
+        self.virtual_count += 1;
+
+        self.virtual_events
+            .borrow_mut()
+            .push(RawGamepadEvent::Connected {
+                device: device.clone(),
+                name: name.to_string(),
+            });
+
+        VirtualGamepad {
+            device,
+            events: self.virtual_events.clone(),
+        }
+    }
+}
+
+/// Gamepad driven from code rather than from hardware.
+/// Behaves exactly as a real gamepad for all the gamepad functions,
+/// useful for tests, demos and on-screen controls.
+///
+/// All changes are visible starting from the next frame.
+/// Dropping the virtual gamepad disconnects it.
+pub struct VirtualGamepad {
+    device: String,
+    events: VirtualEvents,
+}
+
+impl VirtualGamepad {
+    pub fn connect(name: &str) -> VirtualGamepad {
+        get_context().gamepads.connect_virtual(name)
+    }
+
+    pub fn press(&self, button: GamepadButton) {
+        self.button(button, true);
+    }
+
+    pub fn release(&self, button: GamepadButton) {
+        self.button(button, false);
+    }
+
+    /// Set raw axis value, before deadzone is applied
+    pub fn set_axis(&self, axis: GamepadAxis, value: f32) {
+        self.events.borrow_mut().push(RawGamepadEvent::Axis {
+            device: self.device.clone(),
+            axis,
+            value,
+        });
+    }
+
+    fn button(&self, button: GamepadButton, down: bool) {
+        self.events.borrow_mut().push(RawGamepadEvent::Button {
+            device: self.device.clone(),
+            button,
+            down,
+        });
+    }
+}
+
+impl Drop for VirtualGamepad {
+    fn drop(&mut self) {
+        self.events
+            .borrow_mut()
+            .push(RawGamepadEvent::Disconnected {
+                device: self.device.clone(),
+            });
+    }
+}
+
+/// List of currently connected gamepads
+pub fn gamepads() -> Vec<GamepadId> {
+    get_context().gamepads.connected()
+}
+
+/// Human readable gamepad name, as reported by the system
+pub fn gamepad_name(id: GamepadId) -> Option<String> {
+    get_context()
+        .gamepads
+        .get(id)
+        .map(|gamepad| gamepad.name.clone())
+}
+
+pub fn is_gamepad_connected(id: GamepadId) -> bool {
+    get_context().gamepads.get(id).is_some()
+}
+
+/// Gamepads connected or disconnected during the last frame
+pub fn get_gamepad_events() -> Vec<GamepadEvent> {
+    get_context().gamepads.events.clone()
+}
+
+/// Detect if the gamepad button is being pressed
+pub fn is_gamepad_button_down(id: GamepadId, button: GamepadButton) -> bool {
+    get_context().gamepads.is_down(id, button)
+}
+
+/// Detect if the gamepad button has been pressed this frame
+pub fn is_gamepad_button_pressed(id: GamepadId, button: GamepadButton) -> bool {
+    get_context().gamepads.is_pressed(id, button)
+}
+
+/// Detect if the gamepad button has been released this frame
+pub fn is_gamepad_button_released(id: GamepadId, button: GamepadButton) -> bool {
+    get_context().gamepads.is_released(id, button)
+}
+
+/// Analog axis value with the deadzone applied
+pub fn gamepad_axis(id: GamepadId, axis: GamepadAxis) -> f32 {
+    get_context().gamepads.axis(id, axis)
+}
+
+/// Axis values with absolute value less than "deadzone" are reported as 0.
+/// Worn out sticks never return exactly to 0, so the default is `0.1`.
+pub fn set_gamepad_deadzone(axis: GamepadAxis, deadzone: f32) {
+    get_context().gamepads.set_deadzone(axis, deadzone);
+}
+
+/// Linux gamepads through /dev/input event devices.
+#[cfg(target_os = "linux")]
+mod evdev {
+    use super::{GamepadAxis, GamepadBackend, GamepadButton, RawGamepadEvent};
+
+    use std::{
+        collections::{HashMap, HashSet},
+        fs::File,
+        io::{ErrorKind, Read},
+        os::raw::{c_int, c_ulong},
+        os::unix::{
+            fs::{MetadataExt, OpenOptionsExt},
+            io::AsRawFd,
+        },
+    };
+
+    const DEVICES_DIR: &str = "/dev/input";
+    const RESCAN_INTERVAL: f64 = 2.;
+
+    const O_NONBLOCK: i32 = 0o4000;
+
+    const EV_KEY: u16 = 0x01;
+    const EV_ABS: u16 = 0x03;
+
+    const ABS_X: u16 = 0x00;
+    const ABS_Y: u16 = 0x01;
+    const ABS_Z: u16 = 0x02;
+    const ABS_RX: u16 = 0x03;
+    const ABS_RY: u16 = 0x04;
+    const ABS_RZ: u16 = 0x05;
+    const ABS_HAT0X: u16 = 0x10;
+    const ABS_HAT0Y: u16 = 0x11;
+
+    const BTN_JOYSTICK: u16 = 0x120;
+    const BTN_GAMEPAD: u16 = 0x130;
+    const KEY_MAX: usize = 0x2ff;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    /// _IOR('E', nr, size) from linux/input.h
+    const fn eviocg(nr: c_ulong, size: c_ulong) -> c_ulong {
+        (2 << 30) | (size << 16) | ((b'E' as c_ulong) << 8) | nr
+    }
+
+    fn button(code: u16) -> Option<GamepadButton> {
+        Some(match code {
+            0x130 => GamepadButton::South,
+            0x131 => GamepadButton::East,
+            0x133 => GamepadButton::North,
+            0x134 => GamepadButton::West,
+            0x136 => GamepadButton::LeftShoulder,
+            0x137 => GamepadButton::RightShoulder,
+            0x138 => GamepadButton::LeftTrigger,
+            0x139 => GamepadButton::RightTrigger,
+            0x13a => GamepadButton::Select,
+            0x13b => GamepadButton::Start,
+            0x13c => GamepadButton::Mode,
+            0x13d => GamepadButton::LeftThumb,
+            0x13e => GamepadButton::RightThumb,
+            0x220 => GamepadButton::DPadUp,
+            0x221 => GamepadButton::DPadDown,
+            0x222 => GamepadButton::DPadLeft,
+            0x223 => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(code: u16) -> Option<GamepadAxis> {
+        Some(match code {
+            ABS_X => GamepadAxis::LeftX,
+            ABS_Y => GamepadAxis::LeftY,
+            ABS_RX => GamepadAxis::RightX,
+            ABS_RY => GamepadAxis::RightY,
+            ABS_Z => GamepadAxis::LeftTrigger,
+            ABS_RZ => GamepadAxis::RightTrigger,
+            _ => return None,
+        })
+    }
+
+    /// EVIOCGNAME, EVIOCGPHYS and EVIOCGUNIQ, None if empty
+    fn string_ioctl(fd: c_int, nr: c_ulong) -> Option<String> {
+        let mut buffer = [0u8; 256];
+        let len = unsafe { ioctl(fd, eviocg(nr, buffer.len() as _), buffer.as_mut_ptr()) };
+        if len <= 0 {
+            return None;
+        }
+
+        let string = &buffer[..len as usize];
+        let end = string.iter().position(|c| *c == 0).unwrap_or(string.len());
+        Some(String::from_utf8_lossy(&string[..end]).into_owned()).filter(|s| !s.is_empty())
+    }
+
+    struct Device {
+        file: File,
+        // (min, max) for each ABS_* code
+        ranges: HashMap<u16, (i32, i32)>,
+    }
+
+    impl Device {
+        /// None if the device can't be opened or is not a gamepad.
+        /// Also returns a key identifying the device across reconnects and its name.
+        fn open(path: &str) -> Option<(Device, String, String)> {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(O_NONBLOCK)
+                .open(path)
+                .ok()?;
+            let fd = file.as_raw_fd();
+
+            // keyboards and mice are event devices too, gamepads and joysticks have their own buttons
+            let mut keys = [0u8; KEY_MAX / 8 + 1];
+            let len = unsafe {
+                ioctl(
+                    fd,
+                    eviocg(0x20 + EV_KEY as c_ulong, keys.len() as _),
+                    keys.as_mut_ptr(),
+                )
+            };
+            let has_key = |code: u16| keys[code as usize / 8] & (1 << (code % 8)) != 0;
+            if len <= 0 || !(has_key(BTN_GAMEPAD) || has_key(BTN_JOYSTICK)) {
+                return None;
+            }
+
+            let name = string_ioctl(fd, 0x06).unwrap_or_else(|| "Unknown gamepad".to_string());
+
+            // struct input_id: bustype, vendor, product, version
+            let mut id = [0u16; 4];
+            unsafe {
+                ioctl(
+                    fd,
+                    eviocg(0x02, std::mem::size_of_val(&id) as _),
+                    id.as_mut_ptr(),
+                )
+            };
+            // unique id, like the bluetooth address, or the physical port for the wired ones
+            let location = string_ioctl(fd, 0x08)
+                .or_else(|| string_ioctl(fd, 0x07))
+                .unwrap_or_else(|| path.to_string());
+            let key = format!("{:04x}:{:04x}:{}", id[1], id[2], location);
+
+            let mut ranges = HashMap::new();
+            for code in &[
+                ABS_X, ABS_Y, ABS_Z, ABS_RX, ABS_RY, ABS_RZ, ABS_HAT0X, ABS_HAT0Y,
+            ] {
+                // struct input_absinfo: value, minimum, maximum, fuzz, flat, resolution
+                let mut info = [0i32; 6];
+                let res = unsafe {
+                    ioctl(
+                        fd,
+                        eviocg(0x40 + *code as c_ulong, std::mem::size_of_val(&info) as _),
+                        info.as_mut_ptr(),
+                    )
+                };
+                if res >= 0 && info[2] > info[1] {
+                    ranges.insert(*code, (info[1], info[2]));
+                }
+            }
+
+            Some((Device { file, ranges }, key, name))
+        }
+
+        fn normalize(&self, code: u16, value: i32) -> f32 {
+            let trigger = code == ABS_Z || code == ABS_RZ;
+            let (min, max) = self.ranges.get(&code).copied().unwrap_or(if trigger {
+                (0, 255)
+            } else {
+                (-32768, 32767)
+            });
+            let t = (value - min) as f32 / (max - min) as f32;
+
+            if trigger {
+                t.clamp(0., 1.)
+            } else {
+                (t * 2. - 1.).clamp(-1., 1.)
+            }
+        }
+    }
+
+    pub(super) struct EvdevBackend {
+        devices: HashMap<String, Device>,
+        /// Device nodes already checked, gamepads or not, as (path, inode).
+        /// A device plugged in again gets a new node, so it is checked again.
+        checked: HashSet<(String, u64)>,
+        last_scan: Option<f64>,
+    }
+
+    impl EvdevBackend {
+        pub fn new() -> EvdevBackend {
+            EvdevBackend {
+                devices: HashMap::new(),
+                checked: HashSet::new(),
+                last_scan: None,
+            }
+        }
+
+        fn scan(&mut self, events: &mut Vec<RawGamepadEvent>) {
+            let entries = match std::fs::read_dir(DEVICES_DIR) {
+                Ok(entries) => entries,
+                Err(_) => return,
+            };
+
+            let mut present = HashSet::new();
+            for entry in entries.filter_map(|entry| entry.ok()) {
+                if !entry.file_name().to_string_lossy().starts_with("event") {
+                    continue;
+                }
+                let path = entry.path().to_string_lossy().into_owned();
+                let inode = match entry.metadata() {
+                    Ok(metadata) => metadata.ino(),
+                    Err(_) => continue,
+                };
+
+                let node = (path, inode);
+                present.insert(node.clone());
+                if !self.checked.insert(node.clone()) {
+                    continue;
+                }
+
+                if let Some((opened, mut device, name)) = Device::open(&node.0) {
+                    // devices with neither a unique id nor a physical location share the key
+                    if self.devices.contains_key(&device) {
+                        device = format!("{}:{}", device, node.0);
+                    }
+                    self.devices.insert(device.clone(), opened);
+                    events.push(RawGamepadEvent::Connected { device, name });
+                }
+            }
+
+            self.checked.retain(|node| present.contains(node));
+        }
+    }
+
+    impl GamepadBackend for EvdevBackend {
+        fn poll(&mut self, events: &mut Vec<RawGamepadEvent>) {
+            let now = miniquad::date::now();
+            if self
+                .last_scan
+                .map_or(true, |last_scan| now - last_scan > RESCAN_INTERVAL)
+            {
+                self.last_scan = Some(now);
+                self.scan(events);
+            }
+
+            // struct input_event: struct timeval, u16 type, u16 code, i32 value
+            let time_size = std::mem::size_of::<usize>() * 2;
+            let event_size = time_size + 8;
+
+            let mut disconnected = vec![];
+            for (name, device) in &mut self.devices {
+                let mut buffer = [0u8; 64 * 24];
+                let buffer = &mut buffer[..64 * event_size];
+
+                loop {
+                    let read = match device.file.read(buffer) {
+                        Ok(0) => {
+                            disconnected.push(name.clone());
+                            break;
+                        }
+                        Ok(read) => read,
+                        Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                        Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                        Err(_) => {
+                            disconnected.push(name.clone());
+                            break;
+                        }
+                    };
+
+                    for event in buffer[..read].chunks_exact(event_size) {
+                        let event = &event[time_size..];
+                        let kind = u16::from_ne_bytes([event[0], event[1]]);
+                        let code = u16::from_ne_bytes([event[2], event[3]]);
+                        let value = i32::from_ne_bytes([event[4], event[5], event[6], event[7]]);
+
+                        match kind {
+                            EV_KEY => {
+                                if let Some(button) = button(code) {
+                                    events.push(RawGamepadEvent::Button {
+                                        device: name.clone(),
+                                        button,
+                                        down: value != 0,
+                                    });
+                                }
+                            }
+                            EV_ABS if code == ABS_HAT0X || code == ABS_HAT0Y => {
+                                let (negative, positive) = if code == ABS_HAT0X {
+                                    (GamepadButton::DPadLeft, GamepadButton::DPadRight)
+                                } else {
+                                    (GamepadButton::DPadUp, GamepadButton::DPadDown)
+                                };
+                                let value = device.normalize(code, value);
+                                for (button, down) in
+                                    &[(negative, value < -0.5), (positive, value > 0.5)]
+                                {
+                                    events.push(RawGamepadEvent::Button {
+                                        device: name.clone(),
+                                        button: *button,
+                                        down: *down,
+                                    });
+                                }
+                            }
+                            EV_ABS => {
+                                if let Some(axis) = axis(code) {
+                                    events.push(RawGamepadEvent::Axis {
+                                        device: name.clone(),
+                                        axis,
+                                        value: device.normalize(code, value),
+                                    });
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+
+            for device in disconnected {
+                self.devices.remove(&device);
+                events.push(RawGamepadEvent::Disconnected { device });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(gamepads: &mut Gamepads) -> (VirtualGamepad, GamepadId) {
+        let gamepad = gamepads.connect_virtual("test gamepad");
+        gamepads.update();
+
+        let id = *gamepads.connected().last().unwrap();
+        (gamepad, id)
+    }
+
+    #[test]
+    fn buttons() {
+        let mut gamepads = Gamepads::new();
+        let (gamepad, id) = connect(&mut gamepads);
+        gamepads.end_frame();
+
+        gamepad.press(GamepadButton::South);
+        gamepads.update();
+        assert!(gamepads.is_down(id, GamepadButton::South));
+        assert!(gamepads.is_pressed(id, GamepadButton::South));
+        gamepads.end_frame();
+
+        gamepads.update();
+        assert!(gamepads.is_down(id, GamepadButton::South));
+        assert!(!gamepads.is_pressed(id, GamepadButton::South));
+        gamepads.end_frame();
+
+        gamepad.release(GamepadButton::South);
+        gamepads.update();
+        assert!(!gamepads.is_down(id, GamepadButton::South));
+        assert!(gamepads.is_released(id, GamepadButton::South));
+    }
+
+    #[test]
+    fn axes_deadzone() {
+        let mut gamepads = Gamepads::new();
+        let (gamepad, id) = connect(&mut gamepads);
+
+        gamepad.set_axis(GamepadAxis::LeftX, 0.05);
+        gamepad.set_axis(GamepadAxis::RightY, -0.55);
+        gamepads.update();
+        assert_eq!(gamepads.axis(id, GamepadAxis::LeftX), 0.);
+        assert!((gamepads.axis(id, GamepadAxis::RightY) + 0.5).abs() < 1e-5);
+
+        gamepads.set_deadzone(GamepadAxis::LeftX, 0.);
+        assert!((gamepads.axis(id, GamepadAxis::LeftX) - 0.05).abs() < 1e-5);
+    }
+
+    #[test]
+    fn reconnect_keeps_id() {
+        let mut gamepads = Gamepads::new();
+        let (first, first_id) = connect(&mut gamepads);
+        let (second, second_id) = connect(&mut gamepads);
+        assert!(first_id != second_id);
+        gamepads.end_frame();
+
+        first.press(GamepadButton::Start);
+        drop(first);
+        gamepads.update();
+        assert_eq!(gamepads.connected(), vec![second_id]);
+        assert_eq!(gamepads.events, vec![GamepadEvent::Disconnected(first_id)]);
+        assert!(gamepads.is_released(first_id, GamepadButton::Start));
+        gamepads.end_frame();
+
+        gamepads.handle_event(RawGamepadEvent::Connected {
+            device: "virtual-0".to_string(),
+            name: "test gamepad".to_string(),
+        });
+        assert_eq!(gamepads.connected(), vec![first_id, second_id]);
+        assert!(!gamepads.is_down(first_id, GamepadButton::Start));
+        drop(second);
+    }
+}
@@ -0,0 +1,530 @@
+//! Recording of all the input events with frame times, and deterministic replay of the recordings.
+//!
+//! ```ignore
+//! if is_key_pressed(KeyCode::F9) {
+//!     start_recording();
+//! }
+//! if is_key_pressed(KeyCode::F10) {
+//!     if let Some(recording) = stop_recording() {
+//!         recording.save_to_file("bug.replay").unwrap();
+//!     }
+//! }
+//!
+//! // later, maybe in a regression test
+//! let recording = Recording::load_from_file("bug.replay").await.unwrap();
+//! start_replay(recording);
+//! ```
+//!
+//! During the replay all the live keyboard, mouse, touch and resize events are ignored,
+//! and `get_frame_time`/`get_time` are driven by the recorded frame times.
+//! Gamepads are not recorded.
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use super::names::{parse_key_code, parse_mouse_button, parse_touch_phase};
+use super::{KeyCode, KeyMods, MouseButton, TouchPhase};
+use crate::time::Clock;
+use crate::{get_context, Error};
+use glam::{vec2, Vec2};
+
+/// Everything that may reach macroquad from the window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    /// New window size
+    Resize(f32, f32),
+    /// New mouse position
+    MouseMotion(f32, f32),
+    /// Raw mouse movement, dx and dy
+    RawMouseMotion(f32, f32),
+    MouseWheel(f32, f32),
+    /// Button and mouse position
+    MouseDown(MouseButton, f32, f32),
+    /// Button and mouse position
+    MouseUp(MouseButton, f32, f32),
+    /// Phase, touch id and position. Only Started, Moved, Ended and Cancelled phases are expected.
+    Touch(TouchPhase, u64, f32, f32),
+    /// Character, modifiers and is it a repeat
+    Char(char, KeyMods, bool),
+    /// Key, modifiers and is it a repeat
+    KeyDown(KeyCode, KeyMods, bool),
+    KeyUp(KeyCode, KeyMods),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct RecordedFrame {
+    /// Frame time returned by `get_frame_time(Clock::Real)` during this frame
+    frame_time: f64,
+    /// Events delivered right before this frame
+    events: Vec<InputEvent>,
+}
+
+/// Input state at the moment the recording started
+#[derive(Clone, Debug, PartialEq)]
+struct InitialState {
+    mouse_position: Vec2,
+    keys_down: Vec<KeyCode>,
+    mouse_down: Vec<MouseButton>,
+}
+
+#[derive(Debug)]
+pub struct RecordingError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl std::error::Error for RecordingError {}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Recording error at line {}: {}", self.line, self.msg)
+    }
+}
+
+/// Input events of some amount of frames, together with the frame times
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    initial: InitialState,
+    frames: Vec<RecordedFrame>,
+}
+
+const HEADER: &str = "macroquad recording 1";
+
+fn mods_to_string(mods: &KeyMods) -> String {
+    let names = [
+        (mods.shift, "shift"),
+        (mods.ctrl, "ctrl"),
+        (mods.alt, "alt"),
+        (mods.logo, "logo"),
+    ]
+    .iter()
+    .filter(|(active, _)| *active)
+    .map(|(_, name)| *name)
+    .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join("+")
+    }
+}
+
+fn parse_mods(mods: &str) -> Result<KeyMods, String> {
+    let mut res = KeyMods::default();
+
+    for name in mods.split('+').filter(|name| *name != "-") {
+        match name {
+            "shift" => res.shift = true,
+            "ctrl" => res.ctrl = true,
+            "alt" => res.alt = true,
+            "logo" => res.logo = true,
+            _ => return Err(format!("unknown modifier {:?}", name)),
+        }
+    }
+    Ok(res)
+}
+
+fn parse_value<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value {:?}", value))
+}
+
+fn parse_name<T>(parse_fn: fn(&str) -> Option<T>, name: &str) -> Result<T, String> {
+    parse_fn(name).ok_or_else(|| format!("unknown input name {:?}", name))
+}
+
+fn parse_char(code: &str) -> Result<char, String> {
+    std::char::from_u32(parse_value(code)?).ok_or_else(|| format!("invalid char code {}", code))
+}
+
+impl InputEvent {
+    // f32 and f64 Display output is the shortest representation that parses back
+    // into exactly the same value, so the text format is lossless
+    fn to_line(self) -> String {
+        match self {
+            InputEvent::Resize(w, h) => format!("resize {} {}", w, h),
+            InputEvent::MouseMotion(x, y) => format!("mouse_motion {} {}", x, y),
+            InputEvent::RawMouseMotion(dx, dy) => format!("raw_mouse_motion {} {}", dx, dy),
+            InputEvent::MouseWheel(x, y) => format!("mouse_wheel {} {}", x, y),
+            InputEvent::MouseDown(btn, x, y) => format!("mouse_down {:?} {} {}", btn, x, y),
+            InputEvent::MouseUp(btn, x, y) => format!("mouse_up {:?} {} {}", btn, x, y),
+            InputEvent::Touch(phase, id, x, y) => format!("touch {:?} {} {} {}", phase, id, x, y),
+            InputEvent::Char(character, mods, repeat) => format!(
+                "char {} {} {}",
+                character as u32,
+                mods_to_string(&mods),
+                repeat
+            ),
+            InputEvent::KeyDown(key, mods, repeat) => {
+                format!("key_down {:?} {} {}", key, mods_to_string(&mods), repeat)
+            }
+            InputEvent::KeyUp(key, mods) => format!("key_up {:?} {}", key, mods_to_string(&mods)),
+        }
+    }
+
+    fn parse(tokens: &[&str]) -> Result<InputEvent, String> {
+        Ok(match tokens {
+            ["resize", w, h] => InputEvent::Resize(parse_value(w)?, parse_value(h)?),
+            ["mouse_motion", x, y] => InputEvent::MouseMotion(parse_value(x)?, parse_value(y)?),
+            ["raw_mouse_motion", dx, dy] => {
+                InputEvent::RawMouseMotion(parse_value(dx)?, parse_value(dy)?)
+            }
+            ["mouse_wheel", x, y] => InputEvent::MouseWheel(parse_value(x)?, parse_value(y)?),
+            ["mouse_down", btn, x, y] => InputEvent::MouseDown(
+                parse_name(parse_mouse_button, btn)?,
+                parse_value(x)?,
+                parse_value(y)?,
+            ),
+            ["mouse_up", btn, x, y] => InputEvent::MouseUp(
+                parse_name(parse_mouse_button, btn)?,
+                parse_value(x)?,
+                parse_value(y)?,
+            ),
+            ["touch", phase, id, x, y] => InputEvent::Touch(
+                parse_name(parse_touch_phase, phase)?,
+                parse_value(id)?,
+                parse_value(x)?,
+                parse_value(y)?,
+            ),
+            ["char", code, mods, repeat] => {
+                InputEvent::Char(parse_char(code)?, parse_mods(mods)?, parse_value(repeat)?)
+            }
+            ["key_down", key, mods, repeat] => InputEvent::KeyDown(
+                parse_name(parse_key_code, key)?,
+                parse_mods(mods)?,
+                parse_value(repeat)?,
+            ),
+            ["key_up", key, mods] => {
+                InputEvent::KeyUp(parse_name(parse_key_code, key)?, parse_mods(mods)?)
+            }
+            _ => return Err(format!("unknown event {:?}", tokens.join(" "))),
+        })
+    }
+}
+
+impl Recording {
+    /// Amount of recorded frames
+    pub fn frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Serialize the recording into a line-based text format
+    pub fn save(&self) -> String {
+        let mut res = format!("{}\n", HEADER);
+
+        res.push_str(&format!(
+            "mouse_position {} {}\n",
+            self.initial.mouse_position.x(),
+            self.initial.mouse_position.y()
+        ));
+        for key in &self.initial.keys_down {
+            res.push_str(&format!("key_held {:?}\n", key));
+        }
+        for btn in &self.initial.mouse_down {
+            res.push_str(&format!("mouse_held {:?}\n", btn));
+        }
+
+        for frame in &self.frames {
+            res.push_str(&format!("frame {}\n", frame.frame_time));
+            for event in &frame.events {
+                res.push_str(&format!("  {}\n", event.to_line()));
+            }
+        }
+
+        res
+    }
+
+    pub fn save_to_file(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.save())
+    }
+
+    /// Load the file written by `save_to_file`, through `file::load_file`
+    pub async fn load_from_file(path: &str) -> Result<Recording, Error> {
+        let bytes = crate::file::load_file(path).await?;
+        let data = String::from_utf8(bytes).map_err(|_| RecordingError {
+            line: 1,
+            msg: "recording is not a text file".to_string(),
+        })?;
+
+        Ok(Recording::load(&data)?)
+    }
+
+    /// Parse the recording from the text produced by `Recording::save`
+    pub fn load(data: &str) -> Result<Recording, RecordingError> {
+        let mut lines = data.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line.trim()) != Some(HEADER) {
+            return Err(RecordingError {
+                line: 1,
+                msg: format!("expected {:?} header", HEADER),
+            });
+        }
+
+        let mut recording = Recording {
+            initial: InitialState {
+                mouse_position: vec2(0., 0.),
+                keys_down: vec![],
+                mouse_down: vec![],
+            },
+            frames: vec![],
+        };
+
+        for (n, line) in lines {
+            let error = |msg: String| RecordingError { line: n + 1, msg };
+
+            let tokens = line.split_whitespace().collect::<Vec<_>>();
+            match (&tokens[..], recording.frames.last_mut()) {
+                ([], _) => {}
+                (["frame", frame_time], _) => recording.frames.push(RecordedFrame {
+                    frame_time: parse_value(frame_time).map_err(error)?,
+                    events: vec![],
+                }),
+                (["mouse_position", x, y], None) => {
+                    recording.initial.mouse_position = vec2(
+                        parse_value(x).map_err(error)?,
+                        parse_value(y).map_err(error)?,
+                    )
+                }
+                (["key_held", key], None) => recording
+                    .initial
+                    .keys_down
+                    .push(parse_name(parse_key_code, key).map_err(error)?),
+                (["mouse_held", btn], None) => recording
+                    .initial
+                    .mouse_down
+                    .push(parse_name(parse_mouse_button, btn).map_err(error)?),
+                (tokens, Some(frame)) => {
+                    frame.events.push(InputEvent::parse(tokens).map_err(error)?)
+                }
+                (tokens, None) => {
+                    return Err(error(format!(
+                        "expected initial state or frame, got {:?}",
+                        tokens.join(" ")
+                    )))
+                }
+            }
+        }
+
+        Ok(recording)
+    }
+}
+
+pub(crate) struct Recorder {
+    recording: Recording,
+    pending: Vec<InputEvent>,
+}
+
+impl Recorder {
+    pub fn event(&mut self, event: InputEvent) {
+        self.pending.push(event);
+    }
+
+    pub fn begin_frame(&mut self, frame_time: f64) {
+        self.recording.frames.push(RecordedFrame {
+            frame_time,
+            events: std::mem::take(&mut self.pending),
+        });
+    }
+}
+
+pub(crate) struct Player {
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl Player {
+    /// Frame time for the upcoming frame, None when the replay is over
+    pub fn next_frame_time(&self) -> Option<f64> {
+        self.frames.front().map(|frame| frame.frame_time)
+    }
+
+    /// Events for the upcoming frame, None when the replay is over
+    pub fn next_frame(&mut self) -> Option<Vec<InputEvent>> {
+        self.frames.pop_front().map(|frame| frame.events)
+    }
+}
+
+/// Start recording input events, starting from the next frame.
+/// Restarts the recording if it is already in progress.
+pub fn start_recording() {
+    let context = get_context();
+
+    let mut keys_down = context.keys_down.iter().cloned().collect::<Vec<_>>();
+    keys_down.sort_by_key(|key| format!("{:?}", key));
+    let mut mouse_down = context.mouse_down.iter().cloned().collect::<Vec<_>>();
+    mouse_down.sort_by_key(|btn| format!("{:?}", btn));
+
+    context.recorder = Some(Recorder {
+        recording: Recording {
+            initial: InitialState {
+                mouse_position: context.mouse_position,
+                keys_down,
+                mouse_down,
+            },
+            frames: vec![],
+        },
+        pending: vec![],
+    });
+}
+
+/// Stop recording, None if there was no recording in progress
+pub fn stop_recording() -> Option<Recording> {
+    get_context()
+        .recorder
+        .take()
+        .map(|recorder| recorder.recording)
+}
+
+pub fn is_recording() -> bool {
+    get_context().recorder.is_some()
+}
+
+/// Start the replay, starting from the next frame.
+/// Held keys and buttons and mouse position are reset to the state at the start of the recording.
+pub fn start_replay(recording: Recording) {
+    let context = get_context();
+
+    let initial = recording.initial;
+    context.mouse_position = initial.mouse_position;
+    context.last_mouse_position = Some(initial.mouse_position);
+    context.keys_down = initial.keys_down.into_iter().collect();
+    context.mouse_down = initial.mouse_down.into_iter().collect();
+
+    context.player = Some(Player {
+        frames: recording.frames.into(),
+    });
+}
+
+/// Stop the replay and return back to the live input
+pub fn stop_replay() {
+    get_context().player = None;
+}
+
+pub fn is_replaying() -> bool {
+    get_context().player.is_some()
+}
+
+/// Real frame time the upcoming frame should use: recorded one during the replay
+pub(crate) fn next_frame_time(real_frame_time: f64) -> f64 {
+    get_context()
+        .player
+        .as_ref()
+        .and_then(|player| player.next_frame_time())
+        .unwrap_or(real_frame_time)
+}
+
+/// Feed the recorded events into the frame and record the frame, if needed.
+/// Called at the beginning of each frame.
+pub(crate) fn begin_frame() {
+    let context = get_context();
+
+    if let Some(player) = context.player.as_mut() {
+        match player.next_frame() {
+            Some(events) => {
+                for event in events {
+                    context.process_input(event);
+                }
+            }
+            None => context.player = None,
+        }
+    }
+
+    let frame_time = context.clocks.frame_time(Clock::Real);
+    if let Some(recorder) = context.recorder.as_mut() {
+        recorder.begin_frame(frame_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_load() {
+        let mods = KeyMods {
+            shift: true,
+            ctrl: false,
+            alt: true,
+            logo: false,
+        };
+        let recording = Recording {
+            initial: InitialState {
+                mouse_position: vec2(10.5, 0.1),
+                keys_down: vec![KeyCode::LeftShift],
+                mouse_down: vec![MouseButton::Left],
+            },
+            frames: vec![
+                RecordedFrame {
+                    frame_time: 1. / 60.,
+                    events: vec![],
+                },
+                RecordedFrame {
+                    frame_time: 0.1 + 0.2,
+                    events: vec![
+                        InputEvent::Resize(800., 600.),
+                        InputEvent::MouseMotion(1. / 3., -2.7),
+                        InputEvent::RawMouseMotion(-0.5, 12.),
+                        InputEvent::MouseWheel(0., -1.),
+                        InputEvent::MouseDown(MouseButton::Right, 1., 2.),
+                        InputEvent::MouseUp(MouseButton::Middle, 3., 4.),
+                        InputEvent::Touch(TouchPhase::Moved, u64::MAX, 5., 6.),
+                        InputEvent::Char('ы', mods, true),
+                        InputEvent::Char(' ', KeyMods::default(), false),
+                        InputEvent::KeyDown(KeyCode::Space, mods, false),
+                        InputEvent::KeyUp(KeyCode::Kp0, KeyMods::default()),
+                    ],
+                },
+            ],
+        };
+
+        let saved = recording.save();
+        assert_eq!(Recording::load(&saved).unwrap(), recording);
+        assert_eq!(Recording::load(&saved).unwrap().save(), saved);
+    }
+
+    #[test]
+    fn save_load_file() {
+        let path = std::env::temp_dir().join(format!("replay_{}.txt", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let mut runner = crate::headless::Runner::new(100., 100., async move {
+            let recording = Recording {
+                initial: InitialState {
+                    mouse_position: vec2(1., 2.),
+                    keys_down: vec![],
+                    mouse_down: vec![],
+                },
+                frames: vec![RecordedFrame {
+                    frame_time: 0.5,
+                    events: vec![InputEvent::KeyDown(KeyCode::A, KeyMods::default(), false)],
+                }],
+            };
+            recording.save_to_file(&path).unwrap();
+            assert_eq!(Recording::load_from_file(&path).await.unwrap(), recording);
+            std::fs::remove_file(&path).unwrap();
+
+            assert!(matches!(
+                Recording::load_from_file("src/font.png").await,
+                Err(Error::Recording(_))
+            ));
+        });
+
+        runner.run_frames(5);
+        assert!(runner.is_finished());
+    }
+
+    #[test]
+    fn load_errors() {
+        assert_eq!(Recording::load("frame 0.1").unwrap_err().line, 1);
+        assert_eq!(
+            Recording::load("macroquad recording 1\nkey_down A - false")
+                .unwrap_err()
+                .line,
+            2
+        );
+        assert!(
+            Recording::load("macroquad recording 1\nframe 0.1\n  key_down A shift+meta false")
+                .is_err()
+        );
+    }
+}
@@ -39,15 +39,19 @@
 use miniquad::Context as QuadContext;
 use miniquad::*;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::pin::Pin;
 
 mod drawing;
+mod error;
 mod exec;
 
+pub mod assets;
 pub mod camera;
 pub mod file;
+pub mod headless;
+pub mod hot_reload;
 pub mod input;
 pub mod material;
 pub mod models;
@@ -64,6 +68,8 @@ pub mod coroutines;
 
 pub mod prelude;
 
+pub use error::Error;
+
 // TODO: write something about macroquad entrypoint
 #[doc(hidden)]
 pub use macroquad_macro::main;
@@ -81,88 +87,344 @@ pub mod logging {
 
 use drawing::DrawContext;
 use glam::{vec2, Vec2};
+use input::InputEvent;
 use quad_gl::{colors::*, Color};
 
 struct Context {
-    quad_context: QuadContext,
+    /// None in headless mode
+    quad_context: Option<QuadContext>,
 
     screen_width: f32,
     screen_height: f32,
 
     keys_down: HashSet<KeyCode>,
     keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    key_mods: KeyMods,
+    key_events: Vec<input::KeyEvent>,
     mouse_down: HashSet<MouseButton>,
     mouse_pressed: HashSet<MouseButton>,
     mouse_released: HashSet<MouseButton>,
     chars_pressed_queue: Vec<char>,
     mouse_position: Vec2,
+    last_mouse_position: Option<Vec2>,
+    mouse_raw_delta: Vec2,
     mouse_wheel: Vec2,
+    gamepads: input::gamepad::Gamepads,
+    touches: HashMap<u64, input::Touch>,
+    /// Touches that ended during the same frame they started, ended on the next frame
+    delayed_touch_ends: Vec<(u64, input::TouchPhase)>,
+    primary_touch: Option<u64>,
+    simulate_mouse_with_touch: bool,
+    simulate_touch_with_mouse: bool,
+    input_map: input::InputMap,
+    recorder: Option<input::replay::Recorder>,
+    player: Option<input::replay::Player>,
 
     draw_context: DrawContext,
+    /// Draw calls of the last frame, only captured by headless::Runner
+    draw_calls: Vec<quad_gl::DrawCallRecord>,
     coroutines_context: coroutines::CoroutinesContext,
     fonts_storage: text::FontsStorage,
+    /// Only with hot_reload enabled
+    hot_reload: Option<hot_reload::HotReload>,
+    vfs: file::vfs::Vfs,
+    data_storage: file::data::DataStorage,
 
-    start_time: f64,
     last_frame_time: f64,
-    frame_time: f64,
+    clocks: time::Clocks,
+    frame_limiter: time::FrameLimiter,
+    fps_counter: time::FpsCounter,
 }
 
 impl Context {
     const DEFAULT_BG_COLOR: Color = BLACK;
 
-    fn new(mut ctx: QuadContext) -> Context {
+    fn new(ctx: QuadContext) -> Context {
         let (screen_width, screen_height) = ctx.screen_size();
 
+        Context::with_backend(
+            Some(ctx),
+            screen_width,
+            screen_height,
+            input::gamepad::Gamepads::with_platform_backend(),
+        )
+    }
+
+    /// Context without a window and a GPU, for headless::Runner
+    fn headless(screen_width: f32, screen_height: f32) -> Context {
+        Context::with_backend(
+            None,
+            screen_width,
+            screen_height,
+            input::gamepad::Gamepads::new(),
+        )
+    }
+
+    fn with_backend(
+        mut ctx: Option<QuadContext>,
+        screen_width: f32,
+        screen_height: f32,
+        gamepads: input::gamepad::Gamepads,
+    ) -> Context {
+        let mut draw_context = DrawContext::new(ctx.as_mut(), screen_width, screen_height);
+        let fonts_storage = text::FontsStorage::new(ctx.as_mut(), &mut draw_context.gl);
+
         Context {
             screen_width,
             screen_height,
 
             keys_down: HashSet::new(),
             keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            key_mods: KeyMods::default(),
+            key_events: Vec::new(),
             chars_pressed_queue: Vec::new(),
             mouse_down: HashSet::new(),
             mouse_pressed: HashSet::new(),
             mouse_released: HashSet::new(),
             mouse_position: vec2(0., 0.),
+            last_mouse_position: None,
+            mouse_raw_delta: vec2(0., 0.),
             mouse_wheel: vec2(0., 0.),
-
-
-            draw_context: DrawContext::new(&mut ctx),
-            fonts_storage: text::FontsStorage::new(&mut ctx),
+            gamepads,
+            touches: HashMap::new(),
+            delayed_touch_ends: vec![],
+            primary_touch: None,
+            simulate_mouse_with_touch: true,
+            simulate_touch_with_mouse: false,
+            input_map: input::InputMap::new(),
+            recorder: None,
+            player: None,
+
+            draw_context,
+            draw_calls: vec![],
+            fonts_storage,
+            hot_reload: None,
+            vfs: Default::default(),
+            data_storage: file::data::DataStorage::new(ctx.is_none()),
 
             quad_context: ctx,
             coroutines_context: coroutines::CoroutinesContext::new(),
 
-            start_time: miniquad::date::now(),
             last_frame_time: miniquad::date::now(),
-            frame_time: 1. / 60.,
+            clocks: time::Clocks::new(),
+            frame_limiter: time::FrameLimiter::new(),
+            fps_counter: time::FpsCounter::new(),
         }
     }
 
     fn begin_frame(&mut self) {
+        self.gamepads.update();
         self.clear(Self::DEFAULT_BG_COLOR);
         self.draw_context
-            .update_projection_matrix(&mut self.quad_context);
+            .update_projection_matrix(self.screen_width, self.screen_height);
     }
 
     fn end_frame(&mut self) {
         self.draw_context
-            .perform_render_passes(&mut self.quad_context);
+            .perform_render_passes(self.quad_context.as_mut());
+        self.draw_calls = self.draw_context.gl.take_captured_draw_calls();
 
-        self.quad_context.commit_frame();
+        if let Some(ctx) = self.quad_context.as_mut() {
+            ctx.commit_frame();
+        }
 
         self.mouse_wheel = Vec2::new(0., 0.);
+        self.mouse_raw_delta = Vec2::new(0., 0.);
+        if self.last_mouse_position.is_some() {
+            self.last_mouse_position = Some(self.mouse_position);
+        }
         self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.key_events.clear();
         self.mouse_pressed.clear();
         self.mouse_released.clear();
+        self.gamepads.end_frame();
+
+        self.touches.retain(|_, touch| {
+            touch.phase != input::TouchPhase::Ended && touch.phase != input::TouchPhase::Cancelled
+        });
+        for touch in self.touches.values_mut() {
+            touch.phase = input::TouchPhase::Stationary;
+        }
+        for (id, phase) in self.delayed_touch_ends.drain(..) {
+            if let Some(touch) = self.touches.get_mut(&id) {
+                touch.phase = phase;
+            }
+        }
+    }
+
+    /// Modifiers state after "key_code" was released.
+    /// Some platforms still report a modifier as active in its own key up event,
+    /// so modifiers are double-checked against the keys being held.
+    fn released_key_mods(&self, key_code: KeyCode, mut mods: KeyMods) -> KeyMods {
+        let down = |left, right| self.keys_down.contains(&left) || self.keys_down.contains(&right);
+
+        match key_code {
+            KeyCode::LeftShift | KeyCode::RightShift => {
+                mods.shift = down(KeyCode::LeftShift, KeyCode::RightShift)
+            }
+            KeyCode::LeftControl | KeyCode::RightControl => {
+                mods.ctrl = down(KeyCode::LeftControl, KeyCode::RightControl)
+            }
+            KeyCode::LeftAlt | KeyCode::RightAlt => {
+                mods.alt = down(KeyCode::LeftAlt, KeyCode::RightAlt)
+            }
+            KeyCode::LeftSuper | KeyCode::RightSuper => {
+                mods.logo = down(KeyCode::LeftSuper, KeyCode::RightSuper)
+            }
+            _ => {}
+        }
+        mods
+    }
+
+    fn touch(&mut self, phase: input::TouchPhase, id: u64, x: f32, y: f32) {
+        use input::TouchPhase;
+
+        let position = vec2(x, y);
+        let touch = self.touches.entry(id).or_insert(input::Touch {
+            id,
+            phase,
+            position,
+        });
+
+        touch.position = position;
+        if phase == TouchPhase::Started {
+            self.delayed_touch_ends
+                .retain(|(delayed, _)| *delayed != id);
+        }
+        // a touch started this frame is reported as Started for the whole frame
+        match (touch.phase, phase) {
+            (TouchPhase::Started, TouchPhase::Moved) => {}
+            (TouchPhase::Started, TouchPhase::Ended | TouchPhase::Cancelled) => {
+                self.delayed_touch_ends.push((id, phase))
+            }
+            _ => touch.phase = phase,
+        }
+    }
+
+    fn process_input(&mut self, event: InputEvent) {
+        use input::TouchPhase;
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.event(event);
+        }
+
+        match event {
+            InputEvent::Resize(width, height) => {
+                self.screen_width = width;
+                self.screen_height = height;
+                if self.draw_context.gl.framebuffer().is_some() {
+                    self.draw_context
+                        .gl
+                        .enable_rasterizer(width as u16, height as u16);
+                }
+            }
+            InputEvent::MouseMotion(x, y) => {
+                self.mouse_position = Vec2::new(x, y);
+                // no delta for the very first known mouse position
+                if self.last_mouse_position.is_none() {
+                    self.last_mouse_position = Some(self.mouse_position);
+                }
+
+                if self.simulate_touch_with_mouse && self.mouse_down.contains(&MouseButton::Left) {
+                    self.touch(TouchPhase::Moved, input::MOUSE_TOUCH_ID, x, y);
+                }
+            }
+            InputEvent::RawMouseMotion(dx, dy) => {
+                self.mouse_raw_delta += vec2(dx, dy);
+            }
+            InputEvent::MouseWheel(x, y) => {
+                self.mouse_wheel.set_x(x);
+                self.mouse_wheel.set_y(y);
+            }
+            InputEvent::MouseDown(btn, x, y) => {
+                self.mouse_down.insert(btn);
+                self.mouse_pressed.insert(btn);
+
+                if self.simulate_touch_with_mouse && btn == MouseButton::Left {
+                    self.touch(TouchPhase::Started, input::MOUSE_TOUCH_ID, x, y);
+                }
+            }
+            InputEvent::MouseUp(btn, x, y) => {
+                self.mouse_down.remove(&btn);
+                self.mouse_released.insert(btn);
+
+                if self.simulate_touch_with_mouse && btn == MouseButton::Left {
+                    self.touch(TouchPhase::Ended, input::MOUSE_TOUCH_ID, x, y);
+                }
+            }
+            InputEvent::Touch(phase, id, x, y) => {
+                self.touch(phase, id, x, y);
+
+                if self.simulate_mouse_with_touch {
+                    if phase == TouchPhase::Started && self.primary_touch.is_none() {
+                        self.primary_touch = Some(id);
+                        self.mouse_position = vec2(x, y);
+                        // no delta for the jump to a new finger position
+                        self.last_mouse_position = Some(self.mouse_position);
+                        self.mouse_down.insert(MouseButton::Left);
+                        self.mouse_pressed.insert(MouseButton::Left);
+                    } else if self.primary_touch == Some(id) {
+                        self.mouse_position = vec2(x, y);
+
+                        if phase == TouchPhase::Ended || phase == TouchPhase::Cancelled {
+                            self.primary_touch = None;
+                            self.mouse_down.remove(&MouseButton::Left);
+                            self.mouse_released.insert(MouseButton::Left);
+                        }
+                    }
+                }
+            }
+            InputEvent::Char(character, modifiers, _repeat) => {
+                self.key_mods = modifiers;
+                self.chars_pressed_queue.push(character);
+            }
+            InputEvent::KeyDown(keycode, modifiers, repeat) => {
+                self.keys_down.insert(keycode);
+                if repeat == false {
+                    self.keys_pressed.insert(keycode);
+                }
+
+                self.key_mods = modifiers;
+                self.key_events.push(input::KeyEvent {
+                    key_code: keycode,
+                    mods: modifiers,
+                    repeat,
+                    kind: input::KeyEventKind::Down,
+                });
+            }
+            InputEvent::KeyUp(keycode, modifiers) => {
+                self.keys_down.remove(&keycode);
+                self.keys_released.insert(keycode);
+
+                self.key_mods = self.released_key_mods(keycode, modifiers);
+                self.key_events.push(input::KeyEvent {
+                    key_code: keycode,
+                    mods: self.key_mods,
+                    repeat: false,
+                    kind: input::KeyEventKind::Up,
+                });
+            }
+        }
     }
 
     fn clear(&mut self, color: Color) {
-        self.quad_context
-            .clear(Some((color.r, color.g, color.b, color.a)), None, None);
+        if let Some(ctx) = self.quad_context.as_mut() {
+            ctx.clear(Some((color.r, color.g, color.b, color.a)), None, None);
+        }
+        self.draw_context.gl.clear_framebuffer(color);
         self.draw_context.gl.reset();
         self.draw_context
-            .update_projection_matrix(&mut self.quad_context);
+            .update_projection_matrix(self.screen_width, self.screen_height);
+    }
+
+    /// miniquad context, not available in headless mode
+    fn gpu(&mut self) -> &mut QuadContext {
+        self.quad_context
+            .as_mut()
+            .unwrap_or_else(|| panic!("GPU is not available in headless mode"))
     }
 }
 
@@ -175,84 +437,110 @@ fn get_context() -> &'static mut Context {
 
 static mut MAIN_FUTURE: Option<Pin<Box<dyn Future<Output = ()>>>> = None;
 
+/// Run the main future and all the coroutines for one frame.
+/// Returns true when the main future is finished.
+fn run_frame() -> bool {
+    if let Some(future) = unsafe { MAIN_FUTURE.as_mut() } {
+        input::replay::begin_frame();
+        get_context().begin_frame();
+        hot_reload::update();
+
+        if exec::resume(future) {
+            unsafe {
+                MAIN_FUTURE = None;
+            }
+            return true;
+        }
+        coroutines::update();
+    }
+
+    get_context().end_frame();
+
+    false
+}
+
+/// Move all the clocks forward after the frame
+fn advance_time(frame_time: f64) {
+    let context = get_context();
+
+    context
+        .clocks
+        .advance(input::replay::next_frame_time(frame_time));
+    context.fps_counter.push(frame_time);
+}
+
+/// Events from the window, ignored while a recording is being replayed
+fn live_input_event(event: InputEvent) {
+    let context = get_context();
+
+    if context.player.is_none() {
+        context.process_input(event);
+    }
+}
+
 struct Stage {}
 
 impl EventHandlerFree for Stage {
     fn resize_event(&mut self, width: f32, height: f32) {
-        get_context().screen_width = width;
-        get_context().screen_height = height;
+        live_input_event(InputEvent::Resize(width, height));
     }
 
     fn mouse_motion_event(&mut self, x: f32, y: f32) {
-        let context = get_context();
-
-        context.mouse_position = Vec2::new(x, y);
+        live_input_event(InputEvent::MouseMotion(x, y));
+    }
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        live_input_event(InputEvent::RawMouseMotion(dx, dy));
     }
     fn mouse_wheel_event(&mut self, x: f32, y: f32) {
-        let context = get_context();
-
-        context.mouse_wheel.set_x(x);
-        context.mouse_wheel.set_y(y);
+        live_input_event(InputEvent::MouseWheel(x, y));
     }
-    fn mouse_button_down_event(&mut self, btn: MouseButton, _x: f32, _y: f32) {
-        let context = get_context();
-
-        context.mouse_down.insert(btn);
-        context.mouse_pressed.insert(btn);
+    fn mouse_button_down_event(&mut self, btn: MouseButton, x: f32, y: f32) {
+        live_input_event(InputEvent::MouseDown(btn, x, y));
     }
 
-    fn mouse_button_up_event(&mut self, btn: MouseButton, _x: f32, _y: f32) {
-        let context = get_context();
-
-        context.mouse_down.remove(&btn);
-        context.mouse_released.insert(btn);
+    fn mouse_button_up_event(&mut self, btn: MouseButton, x: f32, y: f32) {
+        live_input_event(InputEvent::MouseUp(btn, x, y));
     }
 
-    fn char_event(&mut self, character: char, _modifiers: KeyMods, _repeat: bool) {
-        let context = get_context();
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        live_input_event(InputEvent::Touch(phase.into(), id, x, y));
+    }
 
-        context.chars_pressed_queue.push(character);
+    fn char_event(&mut self, character: char, modifiers: KeyMods, repeat: bool) {
+        live_input_event(InputEvent::Char(character, modifiers, repeat));
     }
 
-    fn key_down_event(&mut self, keycode: KeyCode, _modifiers: KeyMods, repeat: bool) {
-        let context = get_context();
-        context.keys_down.insert(keycode);
-        if repeat == false {
-            context.keys_pressed.insert(keycode);
-        }
+    fn key_down_event(&mut self, keycode: KeyCode, modifiers: KeyMods, repeat: bool) {
+        live_input_event(InputEvent::KeyDown(keycode, modifiers, repeat));
     }
 
-    fn key_up_event(&mut self, keycode: KeyCode, _: KeyMods) {
-        let context = get_context();
-        context.keys_down.remove(&keycode);
+    fn key_up_event(&mut self, keycode: KeyCode, modifiers: KeyMods) {
+        live_input_event(InputEvent::KeyUp(keycode, modifiers));
     }
 
     fn update(&mut self) {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            // TODO: consider making it a part of miniquad? 
+            // TODO: consider making it a part of miniquad?
             std::thread::yield_now();
         }
     }
 
     fn draw(&mut self) {
-        if let Some(future) = unsafe { MAIN_FUTURE.as_mut() } {
-            get_context().begin_frame();
-
-            if exec::resume(future) {
-                unsafe {
-                    MAIN_FUTURE = None;
-                }
-                get_context().quad_context.quit();
-                return;
-            }
-            get_context().coroutines_context.update();
+        if run_frame() {
+            get_context().gpu().quit();
+            return;
         }
 
-        get_context().end_frame();
-
-        get_context().frame_time = date::now() - get_context().last_frame_time;
-        get_context().last_frame_time = date::now();
+        let context = get_context();
+        context
+            .frame_limiter
+            .wait(&mut time::SystemTime, context.last_frame_time);
+
+        let now = date::now();
+        let frame_time = now - context.last_frame_time;
+        context.last_frame_time = now;
+        advance_time(frame_time);
     }
 }
 
@@ -1,6 +1,7 @@
 //! Custom materials - shaders, uniforms.
 
-use crate::get_context;
+use crate::file::load_file;
+use crate::{get_context, Error};
 use miniquad::{PipelineParams, ShaderError, UniformType};
 use quad_gl::GlPipeline;
 
@@ -20,6 +21,37 @@ impl Material {
         context.gl.set_uniform(self.pipeline, name, uniform);
     }
 
+    /// Compile new shaders for this material, keeping it valid along with its uniform values
+    pub(crate) fn rebuild(
+        &self,
+        vertex_shader: &str,
+        fragment_shader: &str,
+        pipeline_params: PipelineParams,
+        uniforms: Vec<(String, UniformType)>,
+    ) -> Result<(), ShaderError> {
+        let context = &mut get_context();
+
+        match context.quad_context.as_mut() {
+            Some(ctx) => context.draw_context.gl.rebuild_pipeline(
+                ctx,
+                self.pipeline,
+                vertex_shader,
+                fragment_shader,
+                pipeline_params,
+                uniforms,
+            ),
+            None => {
+                context.draw_context.gl.rebuild_headless_pipeline(
+                    self.pipeline,
+                    fragment_shader,
+                    pipeline_params,
+                    uniforms,
+                );
+                Ok(())
+            }
+        }
+    }
+
     /// Delete this material. Using deleted material for either rendering
     /// or uniforms manipulation will result internal GL errors.
     pub fn delete(&mut self) {
@@ -57,17 +89,50 @@ pub fn load_material(
 ) -> Result<Material, ShaderError> {
     let context = &mut get_context();
 
-    let pipeline = context.draw_context.gl.make_pipeline(
-        &mut context.quad_context,
-        vertex_shader,
-        fragment_shader,
-        params.pipeline_params,
-        params.uniforms,
-    )?;
+    let pipeline = match context.quad_context.as_mut() {
+        Some(ctx) => context.draw_context.gl.make_pipeline(
+            ctx,
+            vertex_shader,
+            fragment_shader,
+            params.pipeline_params,
+            params.uniforms,
+        )?,
+        None => context.draw_context.gl.make_headless_pipeline(
+            fragment_shader,
+            params.pipeline_params,
+            params.uniforms,
+        ),
+    };
 
     Ok(Material { pipeline })
 }
 
+/// Load material with the shaders from files.
+/// With hot_reload enabled the material is rebuilt when either of the files changes.
+pub async fn load_material_from_files(
+    vertex_path: &str,
+    fragment_path: &str,
+    params: MaterialParams,
+) -> Result<Material, Error> {
+    let vertex_shader = load_file(vertex_path).await?;
+    let fragment_shader = load_file(fragment_path).await?;
+    let vertex_shader = String::from_utf8_lossy(&vertex_shader);
+    let fragment_shader = String::from_utf8_lossy(&fragment_shader);
+
+    let pipeline_params = params.pipeline_params;
+    let uniforms = params.uniforms.clone();
+    let material = load_material(&vertex_shader, &fragment_shader, params)?;
+    crate::hot_reload::watch_material(
+        vertex_path,
+        fragment_path,
+        material,
+        pipeline_params,
+        uniforms,
+    );
+
+    Ok(material)
+}
+
 /// Al followed macroquad rendering calls will use the given material.
 pub fn gl_use_material(material: Material) {
     let context = &mut get_context().draw_context;
@@ -1,5 +1,6 @@
 //! Mose common types that can be glob-imported `use macroquad::prelude::*` for convenience.
 
+pub use crate::assets::{AssetManager, Handle};
 pub use crate::camera::*;
 pub use crate::file::*;
 pub use crate::input::*;
@@ -20,6 +21,7 @@ pub use quad_rand as rand;
 
 pub use crate::collections;
 pub use crate::coroutines;
+pub use crate::hot_reload;
 
 #[cfg(feature = "log-impl")]
 pub use crate::logging::*;
@@ -5,6 +5,7 @@ use std::collections::HashMap;
 use crate::{
     get_context,
     types::{Color, Rect},
+    Error,
 };
 
 use glam::vec2;
@@ -48,20 +49,44 @@ impl FontInternal {
     // pixel gap between glyphs in the atlas
     const GAP: u16 = 2;
 
-    fn load_from_bytes(ctx: &mut miniquad::Context, bytes: &[u8]) -> FontInternal {
+    fn load_from_bytes(
+        ctx: Option<&mut miniquad::Context>,
+        gl: &mut quad_gl::QuadGl,
+        bytes: &[u8],
+    ) -> Result<FontInternal, &'static str> {
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())?;
         let font_image = Image::gen_image_color(512, 512, Color::new(0.0, 0.0, 0.0, 0.0));
-        let font_texture =
-            Texture2D::from_rgba8(ctx, font_image.width, font_image.height, &font_image.bytes);
+        let font_texture = crate::texture::texture_from_image(ctx, gl, &font_image);
 
-        FontInternal {
-            font: fontdue::Font::from_bytes(&bytes[..], fontdue::FontSettings::default()).unwrap(),
+        Ok(FontInternal {
+            font,
             font_image,
             font_texture,
             characters: HashMap::new(),
             cursor_x: 0,
             cursor_y: 0,
             max_line_height: 0,
+        })
+    }
+
+    /// Replace the font data, keeping the atlas texture and re-rasterizing the cached glyphs
+    fn reload(&mut self, bytes: &[u8]) -> Result<(), &'static str> {
+        self.font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())?;
+
+        let characters = self.characters.drain().collect::<Vec<_>>();
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.max_line_height = 0;
+        for pixel in self.font_image.get_image_data_mut() {
+            *pixel = [0; 4];
+        }
+
+        for ((character, size), _) in characters {
+            self.cache_glyph(character, size);
         }
+        crate::texture::update_texture(self.font_texture, &self.font_image);
+
+        Ok(())
     }
 
     fn cache_glyph(&mut self, character: char, size: u16) {
@@ -119,13 +144,7 @@ impl FontInternal {
                 self.font_image.height * 2,
                 Color::new(0.0, 0.0, 0.0, 0.0),
             );
-            let ctx = &mut get_context().quad_context;
-            self.font_texture = Texture2D::from_rgba8(
-                ctx,
-                self.font_image.width,
-                self.font_image.height,
-                &self.font_image.bytes[..],
-            );
+            self.font_texture = crate::texture::load_texture_from_image(&self.font_image);
 
             // recache all previously cached symbols
             for ((character, size), _) in characters {
@@ -142,9 +161,7 @@ impl FontInternal {
                     );
                 }
             }
-            let ctx = &mut get_context().quad_context;
-
-            self.font_texture.update(ctx, &self.font_image);
+            crate::texture::update_texture(self.font_texture, &self.font_image);
         }
     }
 }
@@ -205,9 +222,28 @@ impl Default for TextParams {
 
 /// Load font from file with "path"   
 pub async fn load_ttf_font(path: &str) -> Font {
-    let bytes = crate::file::load_file(path).await.unwrap();
+    try_load_ttf_font(path)
+        .await
+        .unwrap_or_else(|e| panic!("Error loading font: {}", e))
+}
+
+/// Load font from file with "path", file and parsing errors are returned instead of panicking
+pub async fn try_load_ttf_font(path: &str) -> Result<Font, Error> {
+    let bytes = crate::file::load_file(path).await?;
+    let font = try_load_ttf_font_from_bytes(&bytes[..])?;
 
-    load_ttf_font_from_bytes(&bytes[..])
+    crate::hot_reload::watch_font(path, font);
+
+    Ok(font)
+}
+
+/// Reload the font in place, used by hot_reload
+pub(crate) fn reload_font(font: Font, bytes: &[u8]) -> Result<(), Error> {
+    get_context()
+        .fonts_storage
+        .get_font_mut(font)
+        .reload(bytes)
+        .map_err(Error::FontParse)
 }
 
 /// Load font from bytes array, may be use in combination with include_bytes!
@@ -215,16 +251,22 @@ pub async fn load_ttf_font(path: &str) -> Font {
 /// let font = load_ttf_font_from_bytes(include_bytes!("font.ttf"));
 /// ```
 pub fn load_ttf_font_from_bytes(bytes: &[u8]) -> Font {
-    let font = get_context()
-        .fonts_storage
-        .make_font(FontInternal::load_from_bytes(
-            &mut get_context().quad_context,
-            bytes,
-        ));
+    try_load_ttf_font_from_bytes(bytes).unwrap_or_else(|e| panic!("Error loading font: {}", e))
+}
+
+pub fn try_load_ttf_font_from_bytes(bytes: &[u8]) -> Result<Font, Error> {
+    let context = get_context();
+    let font_internal = FontInternal::load_from_bytes(
+        context.quad_context.as_mut(),
+        &mut context.draw_context.gl,
+        bytes,
+    )
+    .map_err(Error::FontParse)?;
+    let font = context.fonts_storage.make_font(font_internal);
 
     font.populate_font_cache(&Font::ascii_character_list(), 15);
 
-    font
+    Ok(font)
 }
 
 /// Draw text with given font_size
@@ -320,8 +362,12 @@ pub(crate) struct FontsStorage {
     fonts: Vec<FontInternal>,
 }
 impl FontsStorage {
-    pub(crate) fn new(ctx: &mut miniquad::Context) -> FontsStorage {
-        let default_font = FontInternal::load_from_bytes(ctx, include_bytes!("ProggyClean.ttf"));
+    pub(crate) fn new(
+        ctx: Option<&mut miniquad::Context>,
+        gl: &mut quad_gl::QuadGl,
+    ) -> FontsStorage {
+        let default_font =
+            FontInternal::load_from_bytes(ctx, gl, include_bytes!("ProggyClean.ttf")).unwrap();
         FontsStorage {
             fonts: vec![default_font],
         }
@@ -1,54 +1,95 @@
 //! Loading and rendering textures. Also render textures, per-pixel image manipluations.
 
-use crate::{file::load_file, get_context, types::Rect};
+use crate::{file::load_file, get_context, types::Rect, Error};
 
 use glam::{vec2, Vec2};
-use quad_gl::{Color, DrawMode, Vertex};
+use quad_gl::{Color, DrawMode, QuadGl, Vertex};
 
 pub use quad_gl::{FilterMode, Image, Texture2D};
 
 /// Load image from file into CPU memory
 pub async fn load_image(path: &str) -> Image {
-    let bytes = load_file(path)
+    try_load_image(path)
         .await
-        .unwrap_or_else(|e| panic!("Error loading texture: {}", e));
+        .unwrap_or_else(|e| panic!("Error loading image: {}", e))
+}
+
+/// Load image from file into CPU memory, file and decoding errors are returned instead of panicking
+pub async fn try_load_image(path: &str) -> Result<Image, Error> {
+    let bytes = load_file(path).await?;
 
-    Image::from_file_with_format(&bytes, None)
+    Ok(Image::try_from_file_with_format(&bytes, None)?)
 }
 
 /// Load texture from file into GPU memory
 pub async fn load_texture(path: &str) -> Texture2D {
-    let bytes = load_file(path)
+    try_load_texture(path)
         .await
-        .unwrap_or_else(|e| panic!("Error loading texture: {}", e));
-    let context = &mut get_context().quad_context;
+        .unwrap_or_else(|e| panic!("Error loading texture: {}", e))
+}
+
+/// Load texture from file into GPU memory, file and decoding errors are returned instead of panicking
+pub async fn try_load_texture(path: &str) -> Result<Texture2D, Error> {
+    let image = try_load_image(path).await?;
+    let texture = load_texture_from_image(&image);
 
-    Texture2D::from_file_with_format(context, &bytes[..], None)
+    crate::hot_reload::watch_texture(path, texture);
+
+    Ok(texture)
 }
 
 /// Unload texture from GPU memory
 /// Using deleted texture will gives different results on different platforms and is not recommended
 pub fn delete_texture(texture: Texture2D) {
-    texture.raw_miniquad_texture_handle().delete()
+    if texture.is_headless() {
+        get_context()
+            .draw_context
+            .gl
+            .delete_headless_texture(texture);
+    } else {
+        texture.raw_miniquad_texture_handle().delete()
+    }
 }
 
 pub fn set_texture_filter(texture: Texture2D, filter_mode: FilterMode) {
-    let context = &mut get_context().quad_context;
-
-    texture.set_filter(context, filter_mode);
+    if let Some(ctx) = get_context().quad_context.as_mut() {
+        texture.set_filter(ctx, filter_mode);
+    }
 }
 
 /// Upload image data to GPU texture
 pub fn update_texture(mut texture: Texture2D, image: &Image) {
-    let context = &mut get_context().quad_context;
+    let context = get_context();
 
-    texture.update(context, image);
+    match context.quad_context.as_mut() {
+        Some(ctx) => texture.update(ctx, image),
+        None => context
+            .draw_context
+            .gl
+            .update_headless_texture(texture, image),
+    }
 }
 
 pub fn load_texture_from_image(image: &Image) -> Texture2D {
-    let context = &mut get_context().quad_context;
+    let context = get_context();
 
-    Texture2D::from_rgba8(context, image.width, image.height, &image.bytes)
+    texture_from_image(
+        context.quad_context.as_mut(),
+        &mut context.draw_context.gl,
+        image,
+    )
+}
+
+/// GPU texture, or CPU one in headless mode
+pub(crate) fn texture_from_image(
+    ctx: Option<&mut miniquad::Context>,
+    gl: &mut QuadGl,
+    image: &Image,
+) -> Texture2D {
+    match ctx {
+        Some(ctx) => Texture2D::from_rgba8(ctx, image.width, image.height, &image.bytes),
+        None => gl.headless_texture(image.width, image.height, &image.bytes),
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -57,8 +98,9 @@ pub struct RenderTarget {
     pub render_pass: miniquad::RenderPass,
 }
 
+/// Render targets are GPU only, so this panics in headless mode
 pub fn render_target(width: u32, height: u32) -> RenderTarget {
-    let context = &mut get_context().quad_context;
+    let context = get_context().gpu();
 
     let texture = miniquad::Texture::new_render_texture(
         context,
@@ -209,22 +251,53 @@ pub fn draw_texture_rec(
 
 /// Get pixel data from GPU texture and return an Image
 pub fn get_texture_data(texture: Texture2D) -> Image {
-    texture.get_texture_data()
+    match get_context().draw_context.gl.headless_texture_data(texture) {
+        Some(image) => image.clone(),
+        None => texture.get_texture_data(),
+    }
+}
+
+/// Save the screen as a PNG file, drawn so far this frame
+pub fn take_screenshot(path: &str) -> std::io::Result<()> {
+    screen_image().save_png(path)
 }
 
-/// Get pixel data from screen buffer and return an Image (screenshot)
+/// "get_screen_data" with the first row at the top of the screen, as in image files
+pub(crate) fn screen_image() -> Image {
+    let mut image = get_screen_data();
+    image.flip_vertical();
+
+    image
+}
+
+/// Get pixel data from screen buffer and return an Image (screenshot).
+/// Rows come in GL order: the first one is the bottom of the screen.
+/// In headless mode the screen is drawn by the CPU rasterizer, see `headless::Runner::enable_rasterizer`
 pub fn get_screen_data() -> Image {
+    let context = get_context();
+    if context.quad_context.is_none() {
+        context.draw_context.perform_render_passes(None);
+
+        return context
+            .draw_context
+            .gl
+            .framebuffer()
+            .cloned()
+            .unwrap_or_else(|| panic!("get_screen_data in headless mode requires the rasterizer"));
+    }
+
     unsafe {
         crate::window::get_internal_gl().flush();
     }
 
     let context = get_context();
+    let (width, height) = (context.screen_width as _, context.screen_height as _);
 
     let texture = Texture2D::from_miniquad_texture(miniquad::Texture::new_render_texture(
-        &mut context.quad_context,
+        context.gpu(),
         miniquad::TextureParams {
-            width: context.screen_width as _,
-            height: context.screen_height as _,
+            width,
+            height,
             ..Default::default()
         },
     ));
@@ -233,3 +306,38 @@ pub fn get_screen_data() -> Image {
 
     get_texture_data(texture)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::Runner;
+
+    #[test]
+    fn screenshot_png() {
+        let mut runner = Runner::new(64., 48., async {
+            loop {
+                crate::shapes::draw_rectangle(10., 10., 20., 20., quad_gl::RED);
+                crate::window::next_frame().await
+            }
+        });
+        runner.enable_rasterizer();
+        runner.step();
+
+        let path = std::env::temp_dir().join(format!("screenshot_{}.png", std::process::id()));
+        let path = path.to_str().unwrap();
+        let load =
+            || Image::try_from_file_with_format(&std::fs::read(path).unwrap(), None).unwrap();
+
+        take_screenshot(path).unwrap();
+        let saved = load();
+        let red: [u8; 4] = quad_gl::RED.into();
+        assert_eq!((saved.width, saved.height), (64, 48));
+        assert_eq!(saved.get_image_data()[15 * 64 + 15], red);
+        assert_eq!(saved.get_image_data()[40 * 64 + 15], [0, 0, 0, 255]);
+
+        // export_png flips the screen data the same way
+        get_screen_data().export_png(path);
+        assert_eq!(load().count_different_pixels(&saved, 0), 0);
+        std::fs::remove_file(path).unwrap();
+    }
+}
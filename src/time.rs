@@ -2,28 +2,512 @@
 
 use crate::get_context;
 
-/// Set target FPS (maximum)
-pub fn set_target_fps(_fps: f32) {
-    unimplemented!()
+/// Pass to `set_target_fps` to disable frame limiting and render as fast as possible.
+pub const NO_FPS_LIMIT: f32 = 0.0;
+
+/// Set target FPS (maximum).
+/// Frames finished faster than 1/fps will wait for the rest of the frame time.
+/// `NO_FPS_LIMIT` (or any non-positive value) removes the limit, which is the default.
+///
+/// On WASM the browser is in charge of the frame rate, so this is a no-op there.
+pub fn set_target_fps(fps: f32) {
+    let context = get_context();
+
+    context.frame_limiter.set_target_fps(fps);
 }
 
-/// Returns current FPS
+/// Returns current FPS, averaged over the last few frames
 pub fn get_fps() -> i32 {
     let context = get_context();
 
-    (1. / context.frame_time) as i32
+    context.fps_counter.fps().round() as i32
 }
 
-/// Returns time in seconds for last frame drawn
+/// Returns time in seconds for last frame drawn, measured on `Clock::Game`
 pub fn get_frame_time() -> f32 {
+    get_clock_frame_time(Clock::Game)
+}
+
+/// Returns elapsed time in seconds since start, measured on `Clock::Game`.
+/// It is slowed down by `set_time_scale` and stops while paused,
+/// use `get_clock_time(Clock::Real)` for the wall clock time since start.
+pub fn get_time() -> f64 {
+    get_clock_time(Clock::Game)
+}
+
+/// Number of frames finished since start
+pub fn get_frame_count() -> u64 {
     let context = get_context();
 
-    context.frame_time as f32
+    context.clocks.frame_count
 }
 
-/// Returns elapsed time in seconds since start
-pub fn get_time() -> f64 {
+/// Time sources available for time queries and time-based coroutines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clock {
+    /// Gameplay time. Affected by `set_time_scale` and `set_paused`.
+    /// Used by default by `get_time`, `get_frame_time` and time-based coroutines.
+    Game,
+    /// Real time, ticking regardless of game pause and time scale.
+    /// Useful for UI and menus that should keep animating while the game is paused.
+    Real,
+}
+
+/// Returns elapsed time in seconds since start, measured on the given clock
+pub fn get_clock_time(clock: Clock) -> f64 {
+    let context = get_context();
+
+    context.clocks.time(clock)
+}
+
+/// Returns time in seconds for last frame drawn, measured on the given clock
+pub fn get_clock_frame_time(clock: Clock) -> f32 {
+    let context = get_context();
+
+    context.clocks.frame_time(clock) as f32
+}
+
+/// Speed of `Clock::Game` relative to real time.
+/// 1.0 is the normal speed, 0.5 is a slow motion, 2.0 is fast forward.
+pub fn set_time_scale(scale: f32) {
+    let context = get_context();
+
+    context.clocks.scale = scale.max(0.) as f64;
+}
+
+/// Current speed of `Clock::Game`, see `set_time_scale`
+pub fn get_time_scale() -> f32 {
+    let context = get_context();
+
+    context.clocks.scale as f32
+}
+
+/// Freeze `Clock::Game`: game time will stop and game frame time will be 0.
+/// `Clock::Real` keeps ticking.
+pub fn set_paused(paused: bool) {
+    let context = get_context();
+
+    context.clocks.paused = paused;
+}
+
+/// Whether `Clock::Game` is frozen by `set_paused`
+pub fn is_paused() -> bool {
     let context = get_context();
 
-    miniquad::date::now() - context.start_time
+    context.clocks.paused
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ClockState {
+    time: f64,
+    frame_time: f64,
+}
+
+/// State of all the clocks, advanced once per frame with real frame time.
+pub(crate) struct Clocks {
+    game: ClockState,
+    real: ClockState,
+    scale: f64,
+    paused: bool,
+    frame_count: u64,
+}
+
+impl Clocks {
+    pub fn new() -> Clocks {
+        let clock = ClockState {
+            time: 0.,
+            frame_time: 1. / 60.,
+        };
+
+        Clocks {
+            game: clock,
+            real: clock,
+            scale: 1.,
+            paused: false,
+            frame_count: 0,
+        }
+    }
+
+    pub fn advance(&mut self, real_frame_time: f64) {
+        let game_frame_time = if self.paused {
+            0.
+        } else {
+            real_frame_time * self.scale
+        };
+
+        self.real.frame_time = real_frame_time;
+        self.real.time += real_frame_time;
+        self.game.frame_time = game_frame_time;
+        self.game.time += game_frame_time;
+        self.frame_count += 1;
+    }
+
+    pub fn time(&self, clock: Clock) -> f64 {
+        match clock {
+            Clock::Game => self.game.time,
+            Clock::Real => self.real.time,
+        }
+    }
+
+    pub fn frame_time(&self, clock: Clock) -> f64 {
+        match clock {
+            Clock::Game => self.game.frame_time,
+            Clock::Real => self.real.frame_time,
+        }
+    }
+}
+
+/// Splits variable frame times into fixed-size simulation steps.
+///
+/// Each frame the frame time is added to an accumulator, and as many
+/// whole steps as fit into it are yielded. What is left over is available
+/// as `alpha` to interpolate rendering between the two last simulation states.
+///
+/// ```ignore
+/// let mut physics = FixedTimestep::new(1. / 120.);
+///
+/// loop {
+///     for dt in physics.steps() {
+///         world.step(dt);
+///     }
+///     world.draw(physics.alpha());
+///
+///     next_frame().await
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: f64,
+    accumulator: f64,
+    max_steps: u32,
+}
+
+impl FixedTimestep {
+    /// Default limit of steps per frame, see `set_max_steps`
+    pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+    // f32 frame times summed up in f64 may miss a whole step by a rounding error
+    const EPSILON: f64 = 1e-7;
+
+    /// "step" is the fixed simulation time step in seconds
+    pub fn new(step: f32) -> FixedTimestep {
+        assert!(step > 0., "Fixed time step should be positive");
+
+        FixedTimestep {
+            step: step as f64,
+            accumulator: 0.,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+        }
+    }
+
+    /// Maximum amount of steps simulated in one frame.
+    /// When simulation is slower than real time, each frame would need more steps
+    /// than the previous one, freezing the game ("spiral of death").
+    /// Time that does not fit into "max_steps" is dropped and simulation slows down instead.
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps;
+    }
+
+    /// Fixed simulation step in seconds
+    pub fn step(&self) -> f32 {
+        self.step as f32
+    }
+
+    /// Steps for the last drawn frame, based on `get_frame_time`
+    pub fn steps(&mut self) -> FixedSteps {
+        self.advance(get_frame_time())
+    }
+
+    /// Steps for the given frame time.
+    /// Same as `steps`, but not tied to the macroquad frame time, useful for tests and replays.
+    pub fn advance(&mut self, frame_time: f32) -> FixedSteps {
+        self.accumulator += frame_time.max(0.) as f64;
+
+        let available = ((self.accumulator + Self::EPSILON) / self.step).floor() as u64;
+        let steps = available.min(self.max_steps as u64);
+
+        self.accumulator = (self.accumulator - steps as f64 * self.step).max(0.);
+        if available > steps {
+            // spiral of death guard: drop the whole steps we can't afford, keep the remainder
+            self.accumulator %= self.step;
+        }
+
+        FixedSteps {
+            remaining: steps as u32,
+            step: self.step as f32,
+        }
+    }
+
+    /// Part of the step accumulated but not simulated yet, in [0..1) range.
+    /// Use it to interpolate between previous and current simulation states while rendering.
+    pub fn alpha(&self) -> f32 {
+        ((self.accumulator / self.step) as f32).min(1.)
+    }
+}
+
+/// Iterator over fixed steps of one frame, yields the step size in seconds.
+#[derive(Debug, Clone)]
+pub struct FixedSteps {
+    remaining: u32,
+    step: f32,
+}
+
+impl Iterator for FixedSteps {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        Some(self.step)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining as usize, Some(self.remaining as usize))
+    }
+}
+
+impl ExactSizeIterator for FixedSteps {}
+
+/// Clock used by the frame limiter.
+/// Is a trait only to make it possible to run the limiter on a fake clock in tests.
+pub(crate) trait TimeSource {
+    fn now(&mut self) -> f64;
+    fn sleep(&mut self, seconds: f64);
+}
+
+pub(crate) struct SystemTime;
+
+impl TimeSource for SystemTime {
+    fn now(&mut self) -> f64 {
+        miniquad::date::now()
+    }
+
+    fn sleep(&mut self, seconds: f64) {
+        #[cfg(not(target_arch = "wasm32"))]
+        std::thread::sleep(std::time::Duration::from_secs_f64(seconds));
+
+        #[cfg(target_arch = "wasm32")]
+        let _ = seconds;
+    }
+}
+
+pub(crate) struct FrameLimiter {
+    target_frame_time: Option<f64>,
+}
+
+impl FrameLimiter {
+    // OS sleep tends to oversleep by a millisecond or so,
+    // so the last bit of the frame is spin-waited
+    const SPIN_TIME: f64 = 0.002;
+
+    pub fn new() -> FrameLimiter {
+        FrameLimiter {
+            target_frame_time: None,
+        }
+    }
+
+    pub fn set_target_fps(&mut self, fps: f32) {
+        self.target_frame_time = if fps > 0. && fps.is_finite() {
+            Some(1. / fps as f64)
+        } else {
+            None
+        };
+    }
+
+    /// Block until the frame started at "frame_start" took at least the target frame time.
+    pub fn wait(&self, time: &mut impl TimeSource, frame_start: f64) {
+        if cfg!(target_arch = "wasm32") {
+            return;
+        }
+
+        let deadline = match self.target_frame_time {
+            Some(frame_time) => frame_start + frame_time,
+            None => return,
+        };
+
+        loop {
+            let remaining = deadline - time.now();
+            if remaining <= 0. {
+                break;
+            }
+            if remaining > Self::SPIN_TIME {
+                time.sleep(remaining - Self::SPIN_TIME);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+}
+
+/// Average FPS over the last `SAMPLES` frames.
+/// Raw 1/frame_time jumps around way too much to be displayed.
+pub(crate) struct FpsCounter {
+    samples: [f64; Self::SAMPLES],
+    next: usize,
+    count: usize,
+}
+
+impl FpsCounter {
+    const SAMPLES: usize = 32;
+
+    pub fn new() -> FpsCounter {
+        FpsCounter {
+            samples: [0.; Self::SAMPLES],
+            next: 0,
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, frame_time: f64) {
+        self.samples[self.next] = frame_time;
+        self.next = (self.next + 1) % Self::SAMPLES;
+        self.count = (self.count + 1).min(Self::SAMPLES);
+    }
+
+    pub fn fps(&self) -> f32 {
+        let total: f64 = self.samples[0..self.count].iter().sum();
+
+        if total <= 0. {
+            return 0.;
+        }
+        (self.count as f64 / total) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fake clock: sleeping oversleeps a bit, like real OS sleep does,
+    /// and each time query takes a little while, like a real spin-wait.
+    struct FakeTime {
+        now: f64,
+        slept: Vec<f64>,
+    }
+
+    impl TimeSource for FakeTime {
+        fn now(&mut self) -> f64 {
+            self.now += 0.0001;
+            self.now
+        }
+
+        fn sleep(&mut self, seconds: f64) {
+            self.slept.push(seconds);
+            self.now += seconds + 0.0005;
+        }
+    }
+
+    #[test]
+    fn limiter_waits_for_target_frame_time() {
+        let mut time = FakeTime {
+            now: 10.005,
+            slept: vec![],
+        };
+        let mut limiter = FrameLimiter::new();
+        limiter.set_target_fps(50.);
+
+        limiter.wait(&mut time, 10.);
+
+        assert!(time.now >= 10.02);
+        assert!(time.now < 10.0205);
+        assert_eq!(time.slept.len(), 1);
+        assert!((time.slept[0] - (0.015 - 0.0001 - FrameLimiter::SPIN_TIME)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn limiter_does_not_wait_for_slow_frames_or_without_limit() {
+        let mut time = FakeTime {
+            now: 10.5,
+            slept: vec![],
+        };
+        let mut limiter = FrameLimiter::new();
+        limiter.wait(&mut time, 10.);
+
+        limiter.set_target_fps(60.);
+        limiter.wait(&mut time, 10.);
+
+        limiter.set_target_fps(NO_FPS_LIMIT);
+        limiter.wait(&mut time, 10.5);
+
+        assert!(time.slept.is_empty());
+        assert!(time.now < 10.501);
+    }
+
+    #[test]
+    fn fps_is_averaged() {
+        let mut counter = FpsCounter::new();
+        assert_eq!(counter.fps(), 0.);
+
+        counter.push(1. / 30.);
+        counter.push(1. / 90.);
+        assert!((counter.fps() - 45.).abs() < 0.01);
+
+        for _ in 0..FpsCounter::SAMPLES {
+            counter.push(1. / 60.);
+        }
+        assert!((counter.fps() - 60.).abs() < 0.01);
+    }
+
+    #[test]
+    fn game_clock_scale_and_pause() {
+        let mut clocks = Clocks::new();
+
+        clocks.advance(0.1);
+        clocks.scale = 0.5;
+        clocks.advance(0.1);
+        assert!((clocks.time(Clock::Game) - 0.15).abs() < 1e-9);
+        assert!((clocks.frame_time(Clock::Game) - 0.05).abs() < 1e-9);
+
+        clocks.paused = true;
+        clocks.advance(0.1);
+        assert!((clocks.time(Clock::Game) - 0.15).abs() < 1e-9);
+        assert_eq!(clocks.frame_time(Clock::Game), 0.);
+
+        assert!((clocks.time(Clock::Real) - 0.3).abs() < 1e-9);
+        assert!((clocks.frame_time(Clock::Real) - 0.1).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fixed_steps_accumulate() {
+        let mut timestep = FixedTimestep::new(1. / 60.);
+
+        let steps = [1. / 120., 1. / 120., 1. / 30., 1. / 144.]
+            .iter()
+            .map(|frame_time| timestep.advance(*frame_time).count())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![0, 1, 2, 0]);
+        assert!((timestep.alpha() - 60. / 144.).abs() < 1e-4);
+
+        assert!(timestep.advance(1. / 60.).all(|dt| dt == 1. / 60.));
+    }
+
+    #[test]
+    fn fixed_steps_are_deterministic() {
+        let frame_times = [0.016, 0.017, 0.033, 0.001, 0.016, 0.05];
+
+        let run = || {
+            let mut timestep = FixedTimestep::new(1. / 100.);
+            frame_times
+                .iter()
+                .map(|frame_time| (timestep.advance(*frame_time).len(), timestep.alpha()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(), run());
+        assert_eq!(run().iter().map(|(n, _)| n).sum::<usize>(), 13);
+    }
+
+    #[test]
+    fn fixed_steps_spiral_of_death_guard() {
+        let mut timestep = FixedTimestep::new(0.01);
+        timestep.set_max_steps(4);
+
+        assert_eq!(timestep.advance(1.005).len(), 4);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-3);
+
+        assert_eq!(timestep.advance(0.005).len(), 1);
+        assert!(timestep.alpha() < 1e-3);
+    }
 }
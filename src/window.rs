@@ -20,12 +20,15 @@ pub fn clear_background(color: Color) {
     // and batching is not clear-friendly
     // so as a workaround we do immediate render pass with clear color
     let clear = PassAction::clear_color(color.r, color.g, color.b, color.a);
-    if let Some(current_pass) = context.draw_context.current_pass {
-        context.quad_context.begin_pass(current_pass, clear);
-    } else {
-        context.quad_context.begin_default_pass(clear);
+    if let Some(quad_context) = context.quad_context.as_mut() {
+        if let Some(current_pass) = context.draw_context.current_pass {
+            quad_context.begin_pass(current_pass, clear);
+        } else {
+            quad_context.begin_default_pass(clear);
+        }
+        quad_context.end_render_pass();
     }
-    context.quad_context.end_render_pass();
+    context.draw_context.gl.clear_framebuffer(color);
 
     context.draw_context.gl.clear_draw_calls();
 }
@@ -43,15 +46,19 @@ impl<'a> InternalGlContext<'a> {
 
         context
             .draw_context
-            .perform_render_passes(&mut self.quad_context);
+            .perform_render_passes(Some(&mut *self.quad_context));
     }
 }
 
+/// Panics in headless mode, where there is no miniquad context
 pub unsafe fn get_internal_gl<'a>() -> InternalGlContext<'a> {
     let context = get_context();
 
     InternalGlContext {
-        quad_context: &mut context.quad_context,
+        quad_context: context
+            .quad_context
+            .as_mut()
+            .unwrap_or_else(|| panic!("GPU is not available in headless mode")),
         quad_gl: &mut context.draw_context.gl,
     }
 }
@@ -10,6 +10,14 @@ pub enum Error {
     },
     TextureNotFound {
         texture: String
+    },
+    /// Map or tileset image could not be loaded
+    Macroquad(macroquad::Error),
+}
+
+impl From<macroquad::Error> for Error {
+    fn from(error: macroquad::Error) -> Error {
+        Error::Macroquad(error)
     }
 }
 
@@ -31,6 +39,7 @@ impl std::fmt::Display for Error {
                 f,
                 "Layer name should be unique to load tiled level in macroquad, non-unique layer name: {}", layer
             ),
+            Error::Macroquad(error) => write!(f, "{}", error),
             
         }
     }
@@ -176,6 +176,28 @@ impl<'a> Iterator for TilesIterator<'a> {
     }
 }
 
+/// Load Tiled map from the json file, along with the tileset images.
+/// Both go through macroquad vfs, images are looked up relative to the map file.
+pub async fn load_map_from_file(path: &str) -> Result<Map, error::Error> {
+    let data = load_file(path)
+        .await
+        .map_err(|err| error::Error::Macroquad(err.into()))?;
+    let data = String::from_utf8_lossy(&data);
+    let map: tiled::Map = DeJson::deserialize_json(&data)?;
+
+    let dir = match path.rfind('/') {
+        Some(n) => &path[..=n],
+        None => "",
+    };
+    let mut textures = vec![];
+    for tileset in &map.tilesets {
+        let texture = try_load_texture(&format!("{}{}", dir, tileset.image)).await?;
+        textures.push((tileset.image.as_str(), texture));
+    }
+
+    load_map(&data, &textures)
+}
+
 /// Load Tiled tileset from given json string
 pub fn load_map(data: &str, textures: &[(&str, Texture2D)]) -> Result<Map, error::Error> {
     let map: tiled::Map = DeJson::deserialize_json(data)?;
@@ -273,3 +295,72 @@ pub fn load_map(data: &str, textures: &[(&str, Texture2D)]) -> Result<Map, error
         raw_tiled_map: map,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::file::vfs::{mount_dir, unmount};
+    use macroquad::headless::Runner;
+
+    const MAP: &str = r#"{
+        "width": 2,
+        "height": 1,
+        "tilewidth": 16,
+        "tileheight": 16,
+        "layers": [
+            { "name": "ground", "type": "tilelayer", "width": 2, "height": 1, "data": [0, 2] }
+        ],
+        "tilesets": [
+            {
+                "name": "tiles",
+                "image": "tiles.png",
+                "columns": 16,
+                "firstgid": 1,
+                "tilecount": 256,
+                "tilewidth": 16,
+                "tileheight": 16
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn load_from_file() {
+        let dir = std::env::temp_dir().join(format!("macroquad_tiled_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("maps")).unwrap();
+        std::fs::write(dir.join("maps/level.json"), MAP).unwrap();
+        std::fs::write(
+            dir.join("maps/broken.json"),
+            MAP.replace("tiles.png", "missing.png"),
+        )
+        .unwrap();
+        // tileset image next to the map, not in the mounted dir root
+        std::fs::copy("../src/font.png", dir.join("maps/tiles.png")).unwrap();
+
+        let mut runner = Runner::new(100., 100., {
+            let dir = dir.clone();
+            async move {
+                mount_dir("assets", dir.to_str().unwrap());
+
+                let map = load_map_from_file("assets/maps/level.json").await.unwrap();
+                assert_eq!(map.tilesets["tiles"].texture.width(), 256.);
+                let ground = &map.layers["ground"];
+                assert!(ground.data[0].is_none());
+                assert_eq!(ground.data[1].as_ref().unwrap().id, 1);
+
+                assert!(matches!(
+                    load_map_from_file("assets/maps/broken.json").await,
+                    Err(error::Error::Macroquad(_))
+                ));
+                assert!(load_map_from_file("assets/maps/missing.json")
+                    .await
+                    .is_err());
+
+                unmount("assets");
+            }
+        });
+
+        runner.run_frames(20);
+        assert!(runner.is_finished());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}